@@ -0,0 +1,289 @@
+//! Command-line interface for creating and watching temporary inboxes.
+//!
+//! Created inboxes are stored in a state file, so later invocations can fetch their messages.
+
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{bail, Result};
+use clap::{Parser, Subcommand, ValueEnum};
+use ephemeral_email::{
+    Domain, ExportFormat, HealthStatus, Inbox, Message, MessageFilter, ProviderType, TempMail,
+};
+use futures::StreamExt;
+use serde_json::json;
+
+mod state;
+
+use state::{State, StoredInbox};
+
+#[derive(Parser)]
+#[command(name = "ephemeral-email", version, about)]
+struct Cli {
+    /// Path of the file storing the created inboxes.
+    #[arg(long, global = true, env = "EPHEMERAL_EMAIL_STATE")]
+    state: Option<PathBuf>,
+    /// Print JSON instead of human-readable output.
+    #[arg(long, global = true)]
+    json: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Create a new inbox and store it.
+    Create {
+        /// The provider to use, e.g. `mail.tm` or `fakemail.net`.
+        #[arg(long)]
+        provider: Option<String>,
+        /// The domain of the email address, either a known domain or a custom one.
+        #[arg(long)]
+        domain: Option<String>,
+        /// The name before the `@` of the email address.
+        #[arg(long)]
+        name: Option<String>,
+        /// Seed for choosing the provider, domain and name, to reproduce a previous run.
+        #[arg(long)]
+        seed: Option<u64>,
+        /// The URL of a Mail.tm-compatible API, like a self-hosted instance.
+        #[arg(long)]
+        api_url: Option<String>,
+    },
+    /// List the stored inboxes.
+    List,
+    /// Print messages as they arrive.
+    Watch {
+        /// The inbox to watch, defaults to the most recently created inbox.
+        address: Option<String>,
+        /// Seconds between polling the inbox.
+        #[arg(long, default_value_t = 5)]
+        interval: u64,
+    },
+    /// Wait for a matching message and print it.
+    Wait {
+        /// The inbox to wait on, defaults to the most recently created inbox.
+        address: Option<String>,
+        /// Only match messages whose sender contains this text.
+        #[arg(long)]
+        from: Option<String>,
+        /// Only match messages whose subject contains this text.
+        #[arg(long)]
+        subject: Option<String>,
+        /// Seconds to wait before giving up.
+        #[arg(long, default_value_t = 120)]
+        timeout: u64,
+    },
+    /// Export all messages of an inbox as JSON, mbox or Maildir.
+    Export {
+        /// The inbox to export, defaults to the most recently created inbox.
+        address: Option<String>,
+        /// The format of the export.
+        #[arg(long, value_enum, default_value_t = Format::Json)]
+        format: Format,
+        /// Write to this file instead of stdout, or to this directory for Maildir.
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Check which providers are currently reachable.
+    Status,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Format {
+    Json,
+    Mbox,
+    Maildir,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    let state_path = match cli.state {
+        Some(path) => path,
+        None => state::default_path()?,
+    };
+    let mut state = State::load(&state_path)?;
+
+    match cli.command {
+        Command::Create {
+            provider,
+            domain,
+            name,
+            seed,
+            api_url,
+        } => {
+            let mut temp_mail = TempMail::new();
+            if let Some(provider) = provider {
+                temp_mail = temp_mail.provider_type(state::parse_provider_type(&provider)?);
+            }
+            if let Some(domain) = domain {
+                temp_mail = temp_mail.domain(Domain::custom(&domain)?);
+            }
+            if let Some(name) = name {
+                temp_mail = temp_mail.name(name);
+            }
+            if let Some(seed) = seed {
+                temp_mail = temp_mail.seed(seed);
+            }
+            if let Some(api_url) = api_url {
+                temp_mail = temp_mail.api_url(api_url);
+            }
+            let inbox = temp_mail.create_inbox().await?;
+            let stored = StoredInbox::from_inbox(&inbox).await;
+            if cli.json {
+                println!("{}", inbox_json(&stored));
+            } else {
+                println!("{}", stored.address);
+            }
+            state.inboxes.push(stored);
+            state.save(&state_path)?;
+        }
+        Command::List => {
+            if cli.json {
+                let inboxes: Vec<_> = state.inboxes.iter().map(inbox_json).collect();
+                println!("{}", serde_json::to_string_pretty(&inboxes)?);
+            } else {
+                for inbox in &state.inboxes {
+                    match inbox.expires_at {
+                        Some(expires_at) => println!(
+                            "{}\t{}\texpires {}",
+                            inbox.address,
+                            inbox.provider,
+                            expires_at.to_rfc3339()
+                        ),
+                        None => println!("{}\t{}", inbox.address, inbox.provider),
+                    }
+                }
+            }
+        }
+        Command::Watch { address, interval } => {
+            let inbox = state.find(address.as_deref())?.resume().await?;
+            let result = inbox.get_new_messages().await;
+            save_session(&mut state, &state_path, &inbox).await?;
+            result?;
+            let mut messages = Box::pin(inbox.watch(Duration::from_secs(interval)));
+            while let Some(message) = messages.next().await {
+                match message {
+                    Ok(message) => print_message(&message, cli.json)?,
+                    Err(e) => eprintln!("Failed to fetch messages: {}", e),
+                }
+                save_session(&mut state, &state_path, &inbox).await?;
+            }
+        }
+        Command::Wait {
+            address,
+            from,
+            subject,
+            timeout,
+        } => {
+            let inbox = state.find(address.as_deref())?.resume().await?;
+            let mut filter = MessageFilter::new();
+            if let Some(from) = from {
+                filter = filter.from(from);
+            }
+            if let Some(subject) = subject {
+                filter = filter.subject(subject);
+            }
+            let result = inbox
+                .wait_for_message(&filter, Duration::from_secs(timeout))
+                .await;
+            save_session(&mut state, &state_path, &inbox).await?;
+            match result? {
+                Some(message) => print_message(&message, cli.json)?,
+                None => bail!("No matching message received within {} seconds", timeout),
+            }
+        }
+        Command::Export {
+            address,
+            format,
+            output,
+        } => {
+            let inbox = state.find(address.as_deref())?.resume().await?;
+            let format = match format {
+                Format::Json => None,
+                Format::Mbox => Some(ExportFormat::Mbox),
+                Format::Maildir => Some(ExportFormat::Maildir),
+            };
+            if let Some(format) = format {
+                let Some(path) = output else {
+                    bail!("The {} export needs an --output path", format);
+                };
+                let result = inbox.export(format, &path).await;
+                save_session(&mut state, &state_path, &inbox).await?;
+                eprintln!("Exported {} messages to {}", result?, path.display());
+                return Ok(());
+            }
+            let result = inbox.get_messages().await;
+            save_session(&mut state, &state_path, &inbox).await?;
+            let content = serde_json::to_string_pretty(&result?)?;
+            match output {
+                Some(path) => std::fs::write(path, content)?,
+                None => println!("{}", content),
+            }
+        }
+        Command::Status => {
+            let statuses = ProviderType::health_check_all().await;
+            if cli.json {
+                let statuses: Vec<_> = statuses.iter().map(status_json).collect();
+                println!("{}", serde_json::to_string_pretty(&statuses)?);
+            } else {
+                for status in &statuses {
+                    println!(
+                        "{}\t{}\t{} ms{}",
+                        status.provider_type,
+                        status.state,
+                        status.latency.as_millis(),
+                        status
+                            .message
+                            .as_ref()
+                            .map(|message| format!("\t{}", message))
+                            .unwrap_or_default()
+                    );
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+fn inbox_json(inbox: &StoredInbox) -> serde_json::Value {
+    json!({
+        "address": inbox.address,
+        "provider": inbox.provider,
+        "created_at": inbox.created_at,
+        "expires_at": inbox.expires_at,
+    })
+}
+
+/// Writes the session of a resumed inbox back to the state file, if refreshing the session
+/// changed its credentials.
+async fn save_session(state: &mut State, path: &Path, inbox: &Inbox) -> Result<()> {
+    if state.update_session(inbox.get_session().await) {
+        state.save(path)?;
+    }
+    Ok(())
+}
+
+fn status_json(status: &HealthStatus) -> serde_json::Value {
+    json!({
+        "provider": status.provider_type,
+        "state": status.state.to_string(),
+        "latency_ms": status.latency.as_millis() as u64,
+        "status_code": status.status_code.map(|code| code.as_u16()),
+        "message": status.message,
+    })
+}
+
+fn print_message(message: &Message, json: bool) -> Result<()> {
+    if json {
+        println!("{}", serde_json::to_string(message)?);
+    } else {
+        println!("From: {}", message.from);
+        println!("Subject: {}", message.subject);
+        println!();
+        println!("{}", message.body);
+        println!();
+    }
+    Ok(())
+}
@@ -0,0 +1,155 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use anyhow::{anyhow, Context, Result};
+use chrono::{DateTime, Utc};
+use ephemeral_email::{EmailAddress, Inbox, InboxSession, ProviderType};
+
+/// An inbox stored in the state file.
+#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+pub struct StoredInbox {
+    pub provider: ProviderType,
+    pub address: EmailAddress,
+    pub credentials: String,
+    pub created_at: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+impl StoredInbox {
+    pub async fn from_inbox(inbox: &Inbox) -> Self {
+        let session = inbox.get_session().await;
+        Self {
+            provider: session.provider_type,
+            address: session.email_address,
+            credentials: session.credentials,
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|duration| duration.as_secs())
+                .unwrap_or_default(),
+            expires_at: session.expires_at,
+        }
+    }
+
+    pub async fn resume(&self) -> Result<Inbox> {
+        let session = InboxSession {
+            provider_type: self.provider,
+            email_address: self.address.clone(),
+            credentials: self.credentials.clone(),
+            expires_at: self.expires_at,
+        };
+        Inbox::resume(session)
+            .await
+            .with_context(|| format!("Failed to resume inbox {}", self.address))
+    }
+}
+
+/// The inboxes persisted between invocations.
+#[derive(Debug, Default, serde::Serialize, serde::Deserialize)]
+pub struct State {
+    pub inboxes: Vec<StoredInbox>,
+}
+
+impl State {
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let content = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read state file {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse state file {}", path.display()))
+    }
+
+    /// Writes the state file, which only the current user may read as it contains credentials.
+    pub fn save(&self, path: &Path) -> Result<()> {
+        if let Some(parent) = path.parent() {
+            create_private_dir(parent)
+                .with_context(|| format!("Failed to create directory {}", parent.display()))?;
+        }
+        write_private(path, serde_json::to_string_pretty(self)?.as_bytes())
+            .with_context(|| format!("Failed to write state file {}", path.display()))
+    }
+
+    /// Stores the session of a resumed inbox, whose credentials change when it is refreshed.
+    ///
+    /// Returns whether the stored inbox changed and the state needs to be saved.
+    pub fn update_session(&mut self, session: InboxSession) -> bool {
+        let Some(inbox) = self
+            .inboxes
+            .iter_mut()
+            .find(|inbox| inbox.address == session.email_address)
+        else {
+            return false;
+        };
+        let changed =
+            inbox.credentials != session.credentials || inbox.expires_at != session.expires_at;
+        inbox.credentials = session.credentials;
+        inbox.expires_at = session.expires_at;
+        changed
+    }
+
+    /// Returns the inbox with the given address, or the most recently created inbox.
+    pub fn find(&self, address: Option<&str>) -> Result<&StoredInbox> {
+        match address {
+            Some(address) => self
+                .inboxes
+                .iter()
+                .find(|inbox| inbox.address.to_string().eq_ignore_ascii_case(address))
+                .ok_or_else(|| anyhow!("No stored inbox with address {}", address)),
+            None => self
+                .inboxes
+                .last()
+                .ok_or_else(|| anyhow!("No stored inboxes, create one with `create` first")),
+        }
+    }
+}
+
+/// Creates `path` and its missing parents, accessible only by the current user on Unix.
+fn create_private_dir(path: &Path) -> std::io::Result<()> {
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    std::os::unix::fs::DirBuilderExt::mode(&mut builder, 0o700);
+    builder.create(path)
+}
+
+/// Writes `contents` to `path`, readable only by the current user on Unix.
+fn write_private(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+    let mut file = options.open(path)?;
+    // The mode only applies to new files, so files written by older versions are restricted too.
+    #[cfg(unix)]
+    file.set_permissions(std::os::unix::fs::PermissionsExt::from_mode(0o600))?;
+    file.write_all(contents)
+}
+
+/// Returns the default location of the state file.
+pub fn default_path() -> Result<PathBuf> {
+    let state_dir = match std::env::var_os("XDG_STATE_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => std::env::var_os("HOME")
+            .map(|home| PathBuf::from(home).join(".local").join("state"))
+            .ok_or_else(|| anyhow!("Cannot determine state directory, use --state"))?,
+    };
+    Ok(state_dir.join("ephemeral-email").join("inboxes.json"))
+}
+
+/// Parses a provider name like `mail.tm` or `FakeMailNet`, ignoring case and punctuation.
+pub fn parse_provider_type(name: &str) -> Result<ProviderType> {
+    name.parse().with_context(|| {
+        format!(
+            "Expected one of: {}",
+            ProviderType::get_all_providers()
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+                .join(", ")
+        )
+    })
+}
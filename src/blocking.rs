@@ -0,0 +1,257 @@
+//! A blocking API for creating inboxes and fetching messages.
+//!
+//! The types in this module mirror [`crate::TempMail`] and [`crate::Inbox`], but their methods block the
+//! current thread instead of returning futures. Each inbox drives its requests on an internal
+//! single-threaded Tokio runtime, so no runtime has to be set up by the caller.
+//!
+//! This module is gated behind the `blocking` feature flag.
+//!
+//! # Panics
+//!
+//! The blocking methods must not be called from within an asynchronous runtime, as Tokio does not
+//! allow blocking on a future from inside a runtime. Use the asynchronous API in that case.
+//!
+//! # Example
+//! ```no_run
+//! use ephemeral_email::blocking::TempMail;
+//! use ephemeral_email::ProviderType;
+//!
+//! let inbox = TempMail::new()
+//!     .provider_type(ProviderType::MailTm)
+//!     .create_inbox()
+//!     .unwrap();
+//! println!("Created inbox with email: {}", inbox.get_email_address());
+//! for message in inbox.get_messages().unwrap() {
+//!     println!("From: {}", message.from);
+//! }
+//! ```
+
+use std::path::Path;
+use std::time::Duration;
+
+use chrono::{DateTime, Utc};
+use rand::RngCore;
+use tokio::runtime::Runtime;
+
+use crate::name::NameGenerator;
+use crate::{
+    Capability, Domain, EmailAddress, ExportError, ExportFormat, InboxCreationError, InboxSession,
+    Message, MessageFetcherError, MessageFilter, MessageSummary, ProviderType,
+};
+
+fn new_runtime() -> Runtime {
+    tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()
+        .expect("failed to build the tokio runtime")
+}
+
+/// A blocking builder for creating temporary email inboxes.
+///
+/// See [`crate::TempMail`] for details on the attributes.
+#[derive(Debug, Clone, Default)]
+pub struct TempMail {
+    inner: crate::TempMail,
+}
+
+impl TempMail {
+    /// Creates a new `TempMail` builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the provider type for the inbox, see [`crate::TempMail::provider_type`].
+    pub fn provider_type(mut self, provider_type: ProviderType) -> Self {
+        self.inner = self.inner.provider_type(provider_type);
+        self
+    }
+
+    /// Specifies the domain for the inbox, see [`crate::TempMail::domain`].
+    pub fn domain(mut self, domain: Domain) -> Self {
+        self.inner = self.inner.domain(domain);
+        self
+    }
+
+    /// Sets the name for the email address, see [`crate::TempMail::name`].
+    pub fn name(mut self, name: impl Into<String>) -> Self {
+        self.inner = self.inner.name(name);
+        self
+    }
+
+    /// Sets the generator for the name, see [`crate::TempMail::name_generator`].
+    pub fn name_generator(mut self, name_generator: impl NameGenerator + 'static) -> Self {
+        self.inner = self.inner.name_generator(name_generator);
+        self
+    }
+
+    /// Points a Mail.tm-compatible provider at a custom API, see [`crate::TempMail::api_url`].
+    pub fn api_url(mut self, url: impl Into<String>) -> Self {
+        self.inner = self.inner.api_url(url);
+        self
+    }
+
+    /// Sets the Mercure hub of a custom API, see [`crate::TempMail::mercure_url`].
+    pub fn mercure_url(mut self, url: impl Into<String>) -> Self {
+        self.inner = self.inner.mercure_url(url);
+        self
+    }
+
+    /// Seeds the random number generator, see [`crate::TempMail::seed`].
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.inner = self.inner.seed(seed);
+        self
+    }
+
+    /// Sets the random number generator, see [`crate::TempMail::rng`].
+    pub fn rng(mut self, rng: impl RngCore + Send + 'static) -> Self {
+        self.inner = self.inner.rng(rng);
+        self
+    }
+
+    /// Requires the provider to support `capability`, see [`crate::TempMail::require`].
+    pub fn require(mut self, capability: Capability) -> Self {
+        self.inner = self.inner.require(capability);
+        self
+    }
+
+    /// Sets how many message details are fetched at the same time, see [`crate::TempMail::fetch_concurrency`].
+    pub fn fetch_concurrency(mut self, fetch_concurrency: usize) -> Self {
+        self.inner = self.inner.fetch_concurrency(fetch_concurrency);
+        self
+    }
+
+    /// Creates a new inbox with the specified attributes, blocking until it is created.
+    /// # Returns
+    /// A new inbox, or an [`InboxCreationError`] error if the inbox could not be created.
+    pub fn create_inbox(self) -> Result<Inbox, InboxCreationError> {
+        let runtime = new_runtime();
+        let inner = runtime.block_on(self.inner.create_inbox())?;
+        Ok(Inbox { inner, runtime })
+    }
+
+    /// Resumes an inbox with the settings of this builder, see [`crate::TempMail::resume_inbox`].
+    pub fn resume_inbox(self, session: InboxSession) -> Result<Inbox, InboxCreationError> {
+        let runtime = new_runtime();
+        let inner = runtime.block_on(self.inner.resume_inbox(session))?;
+        Ok(Inbox { inner, runtime })
+    }
+}
+
+/// A blocking email inbox with the ability to fetch messages.
+///
+/// See [`crate::Inbox`] for the asynchronous version.
+pub struct Inbox {
+    inner: crate::Inbox,
+    runtime: Runtime,
+}
+
+impl Inbox {
+    /// Resumes an inbox from a session previously returned by [`Inbox::get_session`].
+    pub fn resume(session: InboxSession) -> Result<Inbox, InboxCreationError> {
+        let runtime = new_runtime();
+        let inner = runtime.block_on(crate::Inbox::resume(session))?;
+        Ok(Inbox { inner, runtime })
+    }
+
+    /// Returns a reference to the email address associated with this inbox.
+    pub fn get_email_address(&self) -> &EmailAddress {
+        self.inner.get_email_address()
+    }
+
+    /// Returns the type of the provider hosting this inbox.
+    pub fn get_provider_type(&self) -> ProviderType {
+        self.inner.get_provider_type()
+    }
+
+    /// Returns the session of this inbox, which can be stored and passed to [`Inbox::resume`] later.
+    pub fn get_session(&self) -> InboxSession {
+        self.runtime.block_on(self.inner.get_session())
+    }
+
+    /// Returns when the provider deletes the inbox, if known.
+    ///
+    /// See [`crate::Inbox::expires_at`] for details.
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        self.inner.expires_at()
+    }
+
+    /// Extends the lifetime of the inbox at the provider.
+    ///
+    /// See [`crate::Inbox::keep_alive`] for details.
+    pub fn keep_alive(&self) -> Result<(), MessageFetcherError> {
+        self.runtime.block_on(self.inner.keep_alive())
+    }
+
+    /// Fetches the messages from the inbox, blocking until they are received.
+    ///
+    /// See [`crate::Inbox::get_messages`] for details.
+    pub fn get_messages(&self) -> Result<Vec<Message>, MessageFetcherError> {
+        self.runtime.block_on(self.inner.get_messages())
+    }
+
+    /// Fetches the messages that were not returned by a previous call of this method.
+    ///
+    /// See [`crate::Inbox::get_new_messages`] for details.
+    pub fn get_new_messages(&self) -> Result<Vec<Message>, MessageFetcherError> {
+        self.runtime.block_on(self.inner.get_new_messages())
+    }
+
+    /// Fetches the messages matching `filter`.
+    ///
+    /// See [`crate::Inbox::get_messages_matching`] for details.
+    pub fn get_messages_matching(
+        &self,
+        filter: &MessageFilter,
+    ) -> Result<Vec<Message>, MessageFetcherError> {
+        self.runtime
+            .block_on(self.inner.get_messages_matching(filter))
+    }
+
+    /// Waits until a message matching `filter` arrives, or `timeout` passed.
+    ///
+    /// See [`crate::Inbox::wait_for_message`] for details.
+    pub fn wait_for_message(
+        &self,
+        filter: &MessageFilter,
+        timeout: Duration,
+    ) -> Result<Option<Message>, MessageFetcherError> {
+        self.runtime
+            .block_on(self.inner.wait_for_message(filter, timeout))
+    }
+
+    /// Lists the messages in the inbox without their bodies.
+    ///
+    /// See [`crate::Inbox::list_summaries`] for details.
+    pub fn list_summaries(&self) -> Result<Vec<MessageSummary>, MessageFetcherError> {
+        self.runtime.block_on(self.inner.list_summaries())
+    }
+
+    /// Fetches a single message by its [`Message::id`].
+    pub fn fetch_message(&self, id: &str) -> Result<Message, MessageFetcherError> {
+        self.runtime.block_on(self.inner.fetch_message(id))
+    }
+
+    /// Fetches the raw source of a message, if the provider offers it.
+    ///
+    /// See [`crate::Inbox::fetch_source`] for details.
+    pub fn fetch_source(&self, id: &str) -> Result<Option<String>, MessageFetcherError> {
+        self.runtime.block_on(self.inner.fetch_source(id))
+    }
+
+    /// Writes all messages of the inbox to `path` in `format`.
+    ///
+    /// See [`crate::Inbox::export`] for details.
+    pub fn export(
+        &self,
+        format: ExportFormat,
+        path: impl AsRef<Path>,
+    ) -> Result<usize, ExportError> {
+        self.runtime.block_on(self.inner.export(format, path))
+    }
+    /// Deletes the inbox at the provider, blocking until it is deleted.
+    ///
+    /// See [`crate::Inbox::delete`] for details.
+    pub fn delete(self) -> Result<(), MessageFetcherError> {
+        self.runtime.block_on(self.inner.delete())
+    }
+}
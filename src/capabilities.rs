@@ -0,0 +1,131 @@
+use std::fmt::{self, Display, Formatter};
+
+/// A feature that a provider may support, see [`Capabilities`].
+///
+/// This enum is non-exhaustive, additional variants may be added in the future.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum Capability {
+    /// The name before the `@` can be chosen.
+    CustomName,
+    /// The domain can be chosen from the domains of the provider.
+    ChooseDomain,
+    /// Domains not known to this crate can be used with [`crate::Domain::Custom`].
+    CustomDomains,
+    /// Inboxes can be deleted with [`crate::Inbox::delete`].
+    Deletion,
+    /// Attachments of messages are available.
+    Attachments,
+    /// Inboxes can be resumed from an [`crate::InboxSession`].
+    Resumption,
+    /// The lifetime of inboxes can be extended with [`crate::Inbox::keep_alive`].
+    KeepAlive,
+    /// The provider can be pointed at a compatible API with [`crate::TempMail::api_url`].
+    CustomApi,
+}
+
+impl Display for Capability {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            Capability::CustomName => write!(f, "custom names"),
+            Capability::ChooseDomain => write!(f, "choosing a domain"),
+            Capability::CustomDomains => write!(f, "custom domains"),
+            Capability::Deletion => write!(f, "deletion"),
+            Capability::Attachments => write!(f, "attachments"),
+            Capability::Resumption => write!(f, "resumption"),
+            Capability::KeepAlive => write!(f, "keeping inboxes alive"),
+            Capability::CustomApi => write!(f, "custom APIs"),
+        }
+    }
+}
+
+/// The features supported by a provider, as returned by [`crate::ProviderType::capabilities`].
+///
+/// # Example
+/// ```
+/// use ephemeral_email::{Capability, ProviderType};
+///
+/// let providers: Vec<ProviderType> = ProviderType::get_all_providers()
+///     .into_iter()
+///     .filter(|provider_type| provider_type.capabilities().supports(Capability::Deletion))
+///     .collect();
+/// assert!(providers.contains(&ProviderType::MailTm));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub struct Capabilities {
+    /// Whether the name before the `@` can be chosen.
+    pub custom_name: bool,
+    /// Whether the domain can be chosen from the domains of the provider.
+    pub choose_domain: bool,
+    /// Whether domains not known to this crate can be used.
+    pub custom_domains: bool,
+    /// Whether inboxes can be deleted.
+    pub deletion: bool,
+    /// Whether attachments of messages are available.
+    pub attachments: bool,
+    /// Whether inboxes can be resumed from an [`crate::InboxSession`].
+    pub resumption: bool,
+    /// Whether the lifetime of inboxes can be extended.
+    pub keep_alive: bool,
+    /// Whether the provider can be pointed at a compatible API.
+    pub custom_api: bool,
+}
+
+impl Capabilities {
+    /// Supports nothing, the base the capabilities of providers are declared from.
+    pub(crate) const NONE: Capabilities = Capabilities {
+        custom_name: false,
+        choose_domain: false,
+        custom_domains: false,
+        deletion: false,
+        attachments: false,
+        resumption: false,
+        keep_alive: false,
+        custom_api: false,
+    };
+
+    /// Returns whether the provider supports `capability`.
+    pub fn supports(&self, capability: Capability) -> bool {
+        match capability {
+            Capability::CustomName => self.custom_name,
+            Capability::ChooseDomain => self.choose_domain,
+            Capability::CustomDomains => self.custom_domains,
+            Capability::Deletion => self.deletion,
+            Capability::Attachments => self.attachments,
+            Capability::Resumption => self.resumption,
+            Capability::KeepAlive => self.keep_alive,
+            Capability::CustomApi => self.custom_api,
+        }
+    }
+
+    /// Returns the first of `capabilities` the provider does not support.
+    pub fn first_missing(&self, capabilities: &[Capability]) -> Option<Capability> {
+        capabilities
+            .iter()
+            .copied()
+            .find(|&capability| !self.supports(capability))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_first_missing() {
+        let capabilities = Capabilities {
+            custom_name: true,
+            deletion: true,
+            ..Default::default()
+        };
+        assert_eq!(
+            capabilities.first_missing(&[Capability::CustomName, Capability::Deletion]),
+            None
+        );
+        assert_eq!(
+            capabilities.first_missing(&[Capability::CustomName, Capability::Resumption]),
+            Some(Capability::Resumption)
+        );
+    }
+}
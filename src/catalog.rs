@@ -0,0 +1,147 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, OnceLock, RwLock};
+
+use crate::{Domain, DomainCatalogError, ProviderType};
+
+/// The catalog bundled with the crate.
+const BUNDLED: &str = include_str!("domains.json");
+
+static BUNDLED_CATALOG: OnceLock<Arc<DomainCatalog>> = OnceLock::new();
+static INSTALLED_CATALOG: RwLock<Option<Arc<DomainCatalog>>> = RwLock::new(None);
+
+/// A mapping from providers to the domains they can create inboxes with.
+///
+/// The crate bundles a catalog that is used by default. Providers change their domains from time
+/// to time, so an updated catalog can be loaded from disk and installed at runtime, without
+/// waiting for a new release.
+///
+/// A catalog is a JSON object mapping provider ids, see [`ProviderType::id`], to lists of domains:
+///
+/// ```json
+/// {
+///   "tempmail_lol": ["terriblecoffee.org", "underseagolf.com"]
+/// }
+/// ```
+///
+/// Domains matching a [`Domain`] variant are mapped to it, all other domains become
+/// [`Domain::Custom`]. Providers that are not available with the enabled features are ignored.
+///
+/// # Example
+/// ```no_run
+/// use ephemeral_email::{DomainCatalog, ProviderType};
+///
+/// DomainCatalog::load("domains.json").unwrap().install();
+/// println!("{:?}", DomainCatalog::current().domains(ProviderType::TempMailLol));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DomainCatalog {
+    domains: HashMap<ProviderType, Vec<Domain>>,
+}
+
+impl DomainCatalog {
+    /// Returns the catalog bundled with the crate.
+    pub fn bundled() -> Arc<DomainCatalog> {
+        BUNDLED_CATALOG
+            .get_or_init(|| {
+                Arc::new(DomainCatalog::from_json(BUNDLED).expect("bundled catalog is valid"))
+            })
+            .clone()
+    }
+
+    /// Returns the installed catalog, or the bundled one if none was installed.
+    pub fn current() -> Arc<DomainCatalog> {
+        let installed = INSTALLED_CATALOG
+            .read()
+            .unwrap_or_else(|error| error.into_inner());
+        match &*installed {
+            Some(catalog) => catalog.clone(),
+            None => DomainCatalog::bundled(),
+        }
+    }
+
+    /// Parses a catalog from JSON.
+    pub fn from_json(json: &str) -> Result<DomainCatalog, DomainCatalogError> {
+        let entries: HashMap<String, Vec<String>> = serde_json::from_str(json)?;
+        let mut domains = HashMap::new();
+        for (id, names) in entries {
+            let Some(provider_type) = ProviderType::from_id(&id) else {
+                continue;
+            };
+            let names = names
+                .iter()
+                .map(|name| Domain::custom(name))
+                .collect::<Result<Vec<_>, _>>()?;
+            domains.insert(provider_type, names);
+        }
+        Ok(DomainCatalog { domains })
+    }
+
+    /// Loads a catalog from a JSON file.
+    pub fn load(path: impl AsRef<Path>) -> Result<DomainCatalog, DomainCatalogError> {
+        DomainCatalog::from_json(&std::fs::read_to_string(path)?)
+    }
+
+    /// Installs this catalog, replacing the bundled or previously installed one.
+    ///
+    /// Providers created afterwards use the domains of this catalog.
+    pub fn install(self) {
+        *INSTALLED_CATALOG
+            .write()
+            .unwrap_or_else(|error| error.into_inner()) = Some(Arc::new(self));
+    }
+
+    /// Returns the domains of a provider.
+    pub fn domains(&self, provider_type: ProviderType) -> &[Domain] {
+        self.domains
+            .get(&provider_type)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    /// Returns whether any provider has the domain.
+    pub fn contains(&self, domain: &Domain) -> bool {
+        self.domains
+            .values()
+            .any(|domains| domains.contains(domain))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundled_catalog() {
+        let catalog = DomainCatalog::bundled();
+        assert!(catalog
+            .domains(ProviderType::TempMailLol)
+            .contains(&Domain::UnderseaGolfCom));
+        assert!(catalog.domains(ProviderType::MailTm).is_empty());
+    }
+
+    #[test]
+    fn test_catalog_from_json() {
+        let catalog = DomainCatalog::from_json(
+            r#"{"tempmail_lol": ["UnderseaGolf.com", "new.example"], "unknown": ["a.com"]}"#,
+        )
+        .unwrap();
+        assert_eq!(
+            catalog.domains(ProviderType::TempMailLol),
+            [
+                Domain::UnderseaGolfCom,
+                Domain::Custom("new.example".to_string())
+            ]
+        );
+        assert!(catalog.contains(&Domain::Custom("new.example".to_string())));
+        assert!(!catalog.contains(&Domain::Custom("a.com".to_string())));
+    }
+
+    #[test]
+    fn test_catalog_invalid_domain() {
+        assert!(matches!(
+            DomainCatalog::from_json(r#"{"tempmail_lol": ["not a domain"]}"#),
+            Err(DomainCatalogError::InvalidDomain(_))
+        ));
+    }
+}
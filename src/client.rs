@@ -1,5 +1,9 @@
 #[cfg(feature = "use-rquest")]
-pub use rquest::{Client, Error, StatusCode};
+pub use rquest::{
+    cookie::CookieStore, cookie::Jar, Client, Error, RequestBuilder, Response, StatusCode, Url,
+};
 
 #[cfg(not(feature = "use-rquest"))]
-pub use reqwest::{Client, Error, StatusCode};
+pub use reqwest::{
+    cookie::CookieStore, cookie::Jar, Client, Error, RequestBuilder, Response, StatusCode, Url,
+};
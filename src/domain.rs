@@ -1,8 +1,13 @@
 use std::fmt::{self, Display, Formatter};
+use std::str::FromStr;
+
+use crate::{DomainCatalog, DomainError, ProviderType};
 
 /// The email domain to use for the temporary email address.
 ///
-/// This is an enum with all possible domains. To use a custom domain, use the `Custom` variant.
+/// The variants are constants for the domains known at release time. Which provider serves which
+/// domain is defined by the [`DomainCatalog`], which may also contain domains without a variant.
+/// Those, and any other domain, are represented by the `Custom` variant.
 ///
 /// # Example
 /// ```
@@ -14,6 +19,9 @@ use std::fmt::{self, Display, Formatter};
 /// let domain: Domain = "underseagolf.com".into();
 /// assert_eq!(Domain::UnderseaGolfCom, domain);
 ///
+/// let domain: Domain = "UnderseaGolf.com".parse().unwrap();
+/// assert_eq!(Domain::UnderseaGolfCom, domain);
+///
 /// let domain = Domain::Custom("example.com".to_string());
 /// assert_eq!("example.com", domain.to_string());
 /// ```
@@ -145,8 +153,8 @@ pub enum Domain {
 }
 
 impl Domain {
-    pub(crate) fn get_all_domains() -> Vec<Domain> {
-        vec![
+    pub(crate) fn get_all_domains() -> &'static [Domain] {
+        const DOMAINS: &[Domain] = &[
             #[cfg(feature = "use-rquest")]
             Domain::TenMinMailDe,
             #[cfg(feature = "use-rquest")]
@@ -221,108 +229,315 @@ impl Domain {
             Domain::Awesome47,
             Domain::ExpiredToasterOrg,
             Domain::UndeadBankCom,
-        ]
+        ];
+        DOMAINS
     }
-}
 
-impl Display for Domain {
-    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+    /// Returns the domain as a string slice.
+    ///
+    /// # Example
+    /// ```
+    /// use ephemeral_email::Domain;
+    ///
+    /// assert_eq!("underseagolf.com", Domain::UnderseaGolfCom.as_str());
+    /// ```
+    pub fn as_str(&self) -> &str {
         match self {
-            Domain::Custom(custom) => write!(f, "{}", custom),
+            Domain::Custom(custom) => custom,
             #[cfg(feature = "use-rquest")]
-            Domain::TenMinMailDe => write!(f, "10minmail.de"),
+            Domain::TenMinMailDe => "10minmail.de",
             #[cfg(feature = "use-rquest")]
-            Domain::TenMinutenMailXyz => write!(f, "10minutenmail.xyz"),
+            Domain::TenMinutenMailXyz => "10minutenmail.xyz",
             #[cfg(feature = "use-rquest")]
-            Domain::ExistiertNet => write!(f, "existiert.net"),
+            Domain::ExistiertNet => "existiert.net",
             #[cfg(feature = "use-rquest")]
-            Domain::FliegenderFish => write!(f, "fliegender.fish"),
+            Domain::FliegenderFish => "fliegender.fish",
             #[cfg(feature = "use-rquest")]
-            Domain::JagaEmail => write!(f, "jaga.email"),
+            Domain::JagaEmail => "jaga.email",
             #[cfg(feature = "use-rquest")]
-            Domain::MdzEmail => write!(f, "mdz.email"),
+            Domain::MdzEmail => "mdz.email",
             #[cfg(feature = "use-rquest")]
-            Domain::MuellMailCom => write!(f, "muellmail.com"),
+            Domain::MuellMailCom => "muellmail.com",
             #[cfg(feature = "use-rquest")]
-            Domain::MuelleMailCom => write!(f, "muellemail.com"),
+            Domain::MuelleMailCom => "muellemail.com",
             #[cfg(feature = "use-rquest")]
-            Domain::MuellMonster => write!(f, "muell.monster"),
+            Domain::MuellMonster => "muell.monster",
             #[cfg(feature = "use-rquest")]
-            Domain::MuellIcu => write!(f, "muell.icu"),
+            Domain::MuellIcu => "muell.icu",
             #[cfg(feature = "use-rquest")]
-            Domain::MuellIo => write!(f, "muell.io"),
+            Domain::MuellIo => "muell.io",
             #[cfg(feature = "use-rquest")]
-            Domain::MuellXyz => write!(f, "muell.xyz"),
+            Domain::MuellXyz => "muell.xyz",
             #[cfg(feature = "use-rquest")]
-            Domain::MagSpamNet => write!(f, "magspam.net"),
+            Domain::MagSpamNet => "magspam.net",
             #[cfg(feature = "use-rquest")]
-            Domain::FukaruCom => write!(f, "fukaru.com"),
+            Domain::FukaruCom => "fukaru.com",
             #[cfg(feature = "use-rquest")]
-            Domain::OidaIcu => write!(f, "oida.icu"),
+            Domain::OidaIcu => "oida.icu",
             #[cfg(feature = "use-rquest")]
-            Domain::PapierkorbMe => write!(f, "papierkorb.me"),
+            Domain::PapierkorbMe => "papierkorb.me",
             #[cfg(feature = "use-rquest")]
-            Domain::SpamCare => write!(f, "spam.care"),
+            Domain::SpamCare => "spam.care",
             #[cfg(feature = "use-rquest")]
-            Domain::TonneTo => write!(f, "tonne.to"),
+            Domain::TonneTo => "tonne.to",
             #[cfg(feature = "use-rquest")]
-            Domain::UltraFyi => write!(f, "ultra.fyi"),
+            Domain::UltraFyi => "ultra.fyi",
             #[cfg(feature = "use-rquest")]
-            Domain::WegwerfEmailDe => write!(f, "wegwerfemail.de"),
+            Domain::WegwerfEmailDe => "wegwerfemail.de",
             #[cfg(feature = "use-rquest")]
-            Domain::DsgvoParty => write!(f, "dsgvo.party"),
+            Domain::DsgvoParty => "dsgvo.party",
             #[cfg(feature = "use-rquest")]
-            Domain::KnickerbockerbanDe => write!(f, "knickerbockerban.de"),
+            Domain::KnickerbockerbanDe => "knickerbockerban.de",
             #[cfg(feature = "use-rquest")]
-            Domain::LambsauceDe => write!(f, "lambsauce.de"),
+            Domain::LambsauceDe => "lambsauce.de",
             #[cfg(feature = "use-rquest")]
-            Domain::RamenMailDe => write!(f, "ramenmail.de"),
+            Domain::RamenMailDe => "ramenmail.de",
             #[cfg(feature = "use-rquest")]
-            Domain::Ji5De => write!(f, "ji5.de"),
+            Domain::Ji5De => "ji5.de",
             #[cfg(feature = "use-rquest")]
-            Domain::Ji6De => write!(f, "ji6.de"),
+            Domain::Ji6De => "ji6.de",
             #[cfg(feature = "use-rquest")]
-            Domain::Ji7De => write!(f, "ji7.de"),
+            Domain::Ji7De => "ji7.de",
             #[cfg(feature = "use-rquest")]
-            Domain::SudernDe => write!(f, "sudern.de"),
+            Domain::SudernDe => "sudern.de",
             #[cfg(feature = "use-rquest")]
-            Domain::HihiLol => write!(f, "hihi.lol"),
+            Domain::HihiLol => "hihi.lol",
             #[cfg(feature = "use-rquest")]
-            Domain::KeinDate => write!(f, "kein.date"),
+            Domain::KeinDate => "kein.date",
             #[cfg(feature = "use-rquest")]
-            Domain::HolioDay => write!(f, "holio.day"),
+            Domain::HolioDay => "holio.day",
             #[cfg(feature = "use-rquest")]
-            Domain::CornHolioDay => write!(f, "corn.holio.day"),
+            Domain::CornHolioDay => "corn.holio.day",
             #[cfg(feature = "use-rquest")]
-            Domain::BungHolioDay => write!(f, "bung.holio.day"),
+            Domain::BungHolioDay => "bung.holio.day",
             #[cfg(feature = "use-rquest")]
-            Domain::StacysMom => write!(f, "stacys.mom"),
-            Domain::TerribleCoffeeOrg => write!(f, "terriblecoffee.org"),
-            Domain::UnderseaGolfCom => write!(f, "underseagolf.com"),
-            Domain::JailBreakEverythingCom => write!(f, "jailbreakeverything.com"),
-            Domain::Awesome47 => write!(f, "awesome47.com"),
-            Domain::ExpiredToasterOrg => write!(f, "expiredtoaster.org"),
-            Domain::UndeadBankCom => write!(f, "undeadbank.com"),
+            Domain::StacysMom => "stacys.mom",
+            Domain::TerribleCoffeeOrg => "terriblecoffee.org",
+            Domain::UnderseaGolfCom => "underseagolf.com",
+            Domain::JailBreakEverythingCom => "jailbreakeverything.com",
+            Domain::Awesome47 => "awesome47.com",
+            Domain::ExpiredToasterOrg => "expiredtoaster.org",
+            Domain::UndeadBankCom => "undeadbank.com",
         }
     }
+
+    /// Parses a custom domain, validating that it is a valid hostname.
+    ///
+    /// The domain is converted to lowercase. If it is one of the known domains, the matching
+    /// variant is returned instead of [`Domain::Custom`].
+    ///
+    /// # Example
+    /// ```
+    /// use ephemeral_email::Domain;
+    ///
+    /// assert_eq!(
+    ///     Domain::Custom("example.com".to_string()),
+    ///     Domain::custom("Example.com").unwrap()
+    /// );
+    /// assert!(Domain::custom("not a domain").is_err());
+    /// ```
+    pub fn custom(domain: &str) -> Result<Domain, DomainError> {
+        if let Some(domain) = Domain::find_known(domain) {
+            return Ok(domain);
+        }
+        if !is_valid_hostname(domain) {
+            return Err(DomainError::InvalidDomain(domain.to_string()));
+        }
+        Ok(Domain::Custom(domain.to_ascii_lowercase()))
+    }
+
+    /// Returns the provider types that can create inboxes with this domain.
+    ///
+    /// The providers are looked up in the installed [`DomainCatalog`], so domains added by an
+    /// updated catalog map to the providers serving them. A [`Domain::Custom`] not in the catalog
+    /// can be used with the providers supporting custom domains.
+    ///
+    /// # Example
+    /// ```
+    /// use ephemeral_email::{Domain, ProviderType};
+    ///
+    /// assert_eq!(vec![ProviderType::TempMailLol], Domain::UnderseaGolfCom.providers());
+    /// ```
+    pub fn providers(&self) -> Vec<ProviderType> {
+        self.providers_in(&DomainCatalog::current())
+    }
+
+    /// Returns the provider types that can create inboxes with this domain according to
+    /// `catalog`, followed by the providers supporting custom domains for a [`Domain::Custom`].
+    fn providers_in(&self, catalog: &DomainCatalog) -> Vec<ProviderType> {
+        let mut providers: Vec<ProviderType> = ProviderType::get_all_providers()
+            .into_iter()
+            .filter(|provider_type| {
+                provider_type.capabilities().choose_domain
+                    && catalog.domains(*provider_type).contains(self)
+            })
+            .collect();
+        if matches!(self, Domain::Custom(_)) {
+            for provider_type in ProviderType::get_all_providers() {
+                if provider_type.capabilities().custom_domains
+                    && !providers.contains(&provider_type)
+                {
+                    providers.push(provider_type);
+                }
+            }
+        }
+        providers
+    }
+
+    /// Returns the known domain matching `domain`, ignoring case.
+    fn find_known(domain: &str) -> Option<Domain> {
+        Domain::get_all_domains()
+            .iter()
+            .find(|known| known.as_str().eq_ignore_ascii_case(domain))
+            .cloned()
+    }
 }
 
+impl Display for Domain {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Converts a string into a domain, falling back to [`Domain::Custom`] for unknown or invalid
+/// domains. Use [`str::parse`] or [`Domain::custom`] to reject those instead.
 impl From<&str> for Domain {
     fn from(value: &str) -> Self {
-        if let Some(domain) = Domain::get_all_domains()
-            .into_iter()
-            .find(|domain| domain.to_string() == value)
-        {
-            return domain;
+        Domain::find_known(value).unwrap_or_else(|| Domain::Custom(value.to_string()))
+    }
+}
+
+/// Parses one of the known domains, ignoring case.
+///
+/// Known domains are the [`Domain`] variants and the domains of the current [`DomainCatalog`].
+/// Unknown domains are rejected with [`DomainError::UnknownDomain`], use [`Domain::custom`] for
+/// domains not known to this crate.
+///
+/// # Example
+/// ```
+/// use ephemeral_email::{Domain, DomainError};
+///
+/// assert_eq!(Ok(Domain::UnderseaGolfCom), "UnderseaGolf.com".parse());
+/// assert_eq!(
+///     Err(DomainError::UnknownDomain("undersea-golf.com".to_string())),
+///     "undersea-golf.com".parse::<Domain>()
+/// );
+/// ```
+impl FromStr for Domain {
+    type Err = DomainError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if let Some(domain) = Domain::find_known(value) {
+            return Ok(domain);
         }
+        let domain = Domain::Custom(value.to_ascii_lowercase());
+        if DomainCatalog::current().contains(&domain) {
+            return Ok(domain);
+        }
+        Err(DomainError::UnknownDomain(value.to_string()))
+    }
+}
+
+impl TryFrom<String> for Domain {
+    type Error = DomainError;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
 
-        Domain::Custom(value.to_string())
+/// Serializes the domain as its name, like `"mail.tm"`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for Domain {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
     }
 }
+
+/// Deserializes a domain name with [`Domain::custom`], so domains unknown to this crate are
+/// accepted as long as they are valid.
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Domain {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let domain = String::deserialize(deserializer)?;
+        Domain::custom(&domain).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Returns whether `domain` is a valid hostname with at least two labels.
+fn is_valid_hostname(domain: &str) -> bool {
+    let labels: Vec<&str> = domain.split('.').collect();
+    domain.len() <= 253
+        && labels.len() >= 2
+        && labels.iter().all(|label| {
+            (1..=63).contains(&label.len())
+                && label
+                    .bytes()
+                    .all(|byte| byte.is_ascii_alphanumeric() || byte == b'-')
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+        })
+        && labels
+            .last()
+            .is_some_and(|tld| !tld.bytes().all(|byte| byte.is_ascii_digit()))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_known_domains_are_in_catalog() {
+        let catalog = DomainCatalog::bundled();
+        for domain in Domain::get_all_domains() {
+            assert!(catalog.contains(domain), "{} is not in the catalog", domain);
+        }
+    }
+
+    #[test]
+    fn test_providers_from_catalog() {
+        let domain = Domain::Custom("catalog-test.example".to_string());
+        assert_eq!(vec![ProviderType::TempMailLol], domain.providers());
+
+        // Mail.tm cannot choose its domain, so its catalog entries are not used for routing.
+        let catalog = DomainCatalog::from_json(r#"{"mail_tm": ["catalog-test.example"]}"#).unwrap();
+        assert_eq!(
+            vec![ProviderType::TempMailLol],
+            domain.providers_in(&catalog)
+        );
+
+        let catalog =
+            DomainCatalog::from_json(r#"{"tempmail_lol": ["catalog-test.example"]}"#).unwrap();
+        assert_eq!(
+            vec![ProviderType::TempMailLol],
+            domain.providers_in(&catalog)
+        );
+        assert!(Domain::UnderseaGolfCom.providers_in(&catalog).is_empty());
+    }
+
+    #[test]
+    fn test_providers_use_installed_catalog() {
+        let bundled = include_str!("domains.json");
+        assert_eq!(
+            vec![ProviderType::TempMailLol],
+            Domain::TerribleCoffeeOrg.providers()
+        );
+
+        // Moving the domain to Mail.tm, which cannot choose its domain, leaves no provider.
+        let mut moved: serde_json::Value = serde_json::from_str(bundled).unwrap();
+        moved["tempmail_lol"]
+            .as_array_mut()
+            .unwrap()
+            .retain(|domain| domain != "terriblecoffee.org");
+        moved["mail_tm"] = serde_json::json!(["terriblecoffee.org"]);
+        DomainCatalog::from_json(&moved.to_string())
+            .unwrap()
+            .install();
+        let providers = Domain::TerribleCoffeeOrg.providers();
+        DomainCatalog::from_json(bundled).unwrap().install();
+        assert!(providers.is_empty());
+    }
+
     #[test]
     fn test_domain_from_str() {
         assert_eq!(
@@ -335,6 +550,44 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_domain_parse() {
+        assert_eq!(
+            "TerribleCoffee.ORG".parse::<Domain>().unwrap(),
+            Domain::TerribleCoffeeOrg
+        );
+        assert_eq!(
+            "custom.com".parse::<Domain>(),
+            Err(DomainError::UnknownDomain("custom.com".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_domain_custom() {
+        assert_eq!(
+            Domain::custom("Mail.Example-1.com").unwrap(),
+            Domain::Custom("mail.example-1.com".to_string())
+        );
+        assert_eq!(
+            Domain::custom("UNDERSEAGOLF.COM").unwrap(),
+            Domain::UnderseaGolfCom
+        );
+        for invalid in [
+            "",
+            "localhost",
+            "-a.com",
+            "a-.com",
+            "a..com",
+            "a b.com",
+            "1.2.3.4",
+        ] {
+            assert_eq!(
+                Domain::custom(invalid),
+                Err(DomainError::InvalidDomain(invalid.to_string()))
+            );
+        }
+    }
+
     #[test]
     fn test_domain_to_string() {
         assert_eq!(Domain::TerribleCoffeeOrg.to_string(), "terriblecoffee.org");
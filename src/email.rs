@@ -3,18 +3,62 @@ use std::{
     str::FromStr,
 };
 
+use chrono::{DateTime, Utc};
+
 use crate::{domain::Domain, error::EmailAddressError};
 
 /// Represents an email message with a sender, subject, and body.
 /// The body can be plain text or HTML.
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Message {
+    /// The identifier of the message, unique within its inbox.
+    pub id: String,
     /// The sender of the email message.
-    pub from: String,
+    pub from: Mailbox,
+    /// The recipients of the email message.
+    pub to: Vec<Mailbox>,
+    /// The carbon copy recipients, if the provider reports them.
+    pub cc: Vec<Mailbox>,
+    /// The mailboxes replies should be sent to, if the provider reports them.
+    pub reply_to: Vec<Mailbox>,
     /// The subject of the email message.
     pub subject: String,
     /// The main content of the email, which can be plain text or HTML.
     pub body: String,
+    /// The time the message was received, if the provider reports it.
+    pub date: Option<DateTime<Utc>>,
+}
+
+/// A summary of an email message without its body.
+///
+/// Summaries are returned by [`crate::Inbox::list_summaries`], which is cheaper than fetching
+/// the full messages. Use [`crate::Inbox::fetch_message`] to get the full message.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MessageSummary {
+    /// The identifier of the message, unique within its inbox.
+    pub id: String,
+    /// The sender of the email message.
+    pub from: Mailbox,
+    /// The subject of the email message.
+    pub subject: String,
+    /// The time the message was received, if the provider reports it.
+    pub date: Option<DateTime<Utc>>,
+    /// The size of the message in bytes, if the provider reports it.
+    pub size: Option<u64>,
+}
+
+impl From<&Message> for MessageSummary {
+    fn from(message: &Message) -> Self {
+        Self {
+            id: message.id.clone(),
+            from: message.from.clone(),
+            subject: message.subject.clone(),
+            date: message.date,
+            size: None,
+        }
+    }
 }
 
 /// Represents an email address with a name and domain.
@@ -34,6 +78,9 @@ pub struct Message {
 ///
 /// let email: Result<EmailAddress, _> = "invalid-email".parse();
 /// assert!(email.is_err());
+///
+/// let email: EmailAddress = "First.Last@Example.com".parse().unwrap();
+/// assert_eq!("First.Last@example.com", email.to_string());
 /// ```
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub struct EmailAddress {
@@ -56,21 +103,252 @@ impl Display for EmailAddress {
     }
 }
 
+/// Serializes the address as a string, like `"test@mail.tm"`.
+#[cfg(feature = "serde")]
+impl serde::Serialize for EmailAddress {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Deserializes and validates an address string, see [`EmailAddress::from_str`].
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for EmailAddress {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let address = String::deserialize(deserializer)?;
+        address.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+/// The maximum length of an address, from RFC 5321.
+const MAX_LENGTH: usize = 254;
+/// The maximum length of the part before the `@`, from RFC 5321.
+const MAX_LOCAL_PART_LENGTH: usize = 64;
+
+/// Parses and validates an email address following RFC 5321 and RFC 5322.
+///
+/// The part before the `@` may be a dot-atom like `first.last+tag` or a quoted string like
+/// `"first last"`, and keeps its case. The domain must be a valid hostname and is converted to
+/// lowercase. Comments, address literals and non-ASCII addresses are not supported.
 impl FromStr for EmailAddress {
     type Err = EmailAddressError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
+        // A quoted local part may contain an `@`, but a domain never does.
         let (name, domain) = s
-            .split_once('@')
+            .rsplit_once('@')
             .ok_or(EmailAddressError::InvalidEmailAddress(s.into()))?;
+        if s.len() > MAX_LENGTH {
+            return Err(EmailAddressError::TooLong(s.len()));
+        }
+        validate_local_part(name)?;
 
         Ok(Self {
             name: name.into(),
-            domain: domain.into(),
+            domain: Domain::custom(domain)?,
         })
     }
 }
 
+fn validate_local_part(name: &str) -> Result<(), EmailAddressError> {
+    if name.is_empty() {
+        return Err(EmailAddressError::EmptyLocalPart);
+    }
+    if name.len() > MAX_LOCAL_PART_LENGTH {
+        return Err(EmailAddressError::LocalPartTooLong(name.len()));
+    }
+    let valid = match name
+        .strip_prefix('"')
+        .and_then(|name| name.strip_suffix('"'))
+    {
+        Some(quoted) => is_quoted_string(quoted),
+        None => is_dot_atom(name),
+    };
+    if !valid {
+        return Err(EmailAddressError::InvalidLocalPart(name.into()));
+    }
+    Ok(())
+}
+
+/// Returns whether `name` is a dot-atom, a dot separated sequence of non-empty atoms.
+fn is_dot_atom(name: &str) -> bool {
+    name.split('.').all(|atom| {
+        !atom.is_empty()
+            && atom
+                .bytes()
+                .all(|byte| byte.is_ascii_alphanumeric() || b"!#$%&'*+-/=?^_`{|}~".contains(&byte))
+    })
+}
+
+/// Returns whether `content` is valid between the quotes of a quoted string.
+fn is_quoted_string(content: &str) -> bool {
+    let mut bytes = content.bytes();
+    while let Some(byte) = bytes.next() {
+        match byte {
+            b'\\' => {
+                if !bytes
+                    .next()
+                    .is_some_and(|escaped| (b' '..=b'~').contains(&escaped))
+                {
+                    return false;
+                }
+            }
+            b'"' => return false,
+            b' '..=b'~' => {}
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// A mailbox in a message header, an email address with an optional display name.
+///
+/// # Example
+/// ```
+/// use ephemeral_email::Mailbox;
+///
+/// let mailbox: Mailbox = "\"Doe, Jane\" <jane@example.com>".parse().unwrap();
+/// assert_eq!(Some("Doe, Jane"), mailbox.name.as_deref());
+/// assert_eq!("jane@example.com", mailbox.address.to_string());
+/// assert_eq!("\"Doe, Jane\" <jane@example.com>", mailbox.to_string());
+///
+/// let mailbox: Mailbox = "jane@example.com".parse().unwrap();
+/// assert_eq!(None, mailbox.name);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Mailbox {
+    /// The display name, like `Jane Doe` in `Jane Doe <jane@example.com>`.
+    pub name: Option<String>,
+    /// The email address.
+    pub address: EmailAddress,
+}
+
+impl Mailbox {
+    pub fn new(name: Option<String>, address: EmailAddress) -> Self {
+        Self {
+            name: name.filter(|name| !name.trim().is_empty()),
+            address,
+        }
+    }
+
+    /// Parses a mailbox as reported by a provider, accepting addresses that are not valid.
+    ///
+    /// Providers report senders as they were received, so the address is only validated as far
+    /// as possible. A header without any address is kept as the name of `unknown@invalid`.
+    pub(crate) fn from_header(value: &str) -> Self {
+        if let Ok(mailbox) = value.parse() {
+            return mailbox;
+        }
+        let (name, address) = split_mailbox(value);
+        match address.rsplit_once('@') {
+            Some((local_part, domain)) => Mailbox::new(
+                name,
+                EmailAddress::new(local_part, Domain::from(domain.to_lowercase().as_str())),
+            ),
+            None => Mailbox::new(
+                Some(value.trim().to_string()),
+                EmailAddress::new("unknown", Domain::Custom("invalid".to_string())),
+            ),
+        }
+    }
+
+    /// Parses a comma separated list of mailboxes as reported by a provider, see
+    /// [`Mailbox::from_header`].
+    pub(crate) fn list_from_header(value: &str) -> Vec<Self> {
+        let mut mailboxes = Vec::new();
+        let mut start = 0;
+        let mut quoted = false;
+        let mut escaped = false;
+        for (index, c) in value.char_indices() {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' if quoted => escaped = true,
+                '"' => quoted = !quoted,
+                ',' if !quoted => {
+                    mailboxes.push(&value[start..index]);
+                    start = index + 1;
+                }
+                _ => {}
+            }
+        }
+        mailboxes.push(&value[start..]);
+        mailboxes
+            .into_iter()
+            .filter(|mailbox| !mailbox.trim().is_empty())
+            .map(Mailbox::from_header)
+            .collect()
+    }
+}
+
+impl From<EmailAddress> for Mailbox {
+    fn from(address: EmailAddress) -> Self {
+        Self {
+            name: None,
+            address,
+        }
+    }
+}
+
+impl Display for Mailbox {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let Some(name) = &self.name else {
+            return write!(f, "{}", self.address);
+        };
+        let is_phrase = name
+            .split(' ')
+            .all(|word| !word.is_empty() && is_dot_atom(word) && !word.contains('.'));
+        if is_phrase {
+            write!(f, "{} <{}>", name, self.address)
+        } else {
+            let name = name.replace('\\', "\\\\").replace('"', "\\\"");
+            write!(f, "\"{}\" <{}>", name, self.address)
+        }
+    }
+}
+
+/// Parses a mailbox like `Jane Doe <jane@example.com>`, `"Doe, Jane" <jane@example.com>` or
+/// `jane@example.com`, and validates the address like [`EmailAddress::from_str`].
+impl FromStr for Mailbox {
+    type Err = EmailAddressError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, address) = split_mailbox(s);
+        Ok(Mailbox::new(name, address.parse()?))
+    }
+}
+
+/// Splits a mailbox into the unquoted display name and the address.
+fn split_mailbox(value: &str) -> (Option<String>, &str) {
+    let value = value.trim();
+    let Some(address) = value
+        .strip_suffix('>')
+        .and_then(|value| value.rsplit_once('<'))
+    else {
+        return (None, value);
+    };
+    let name = address.0.trim();
+    let name = match name
+        .strip_prefix('"')
+        .and_then(|name| name.strip_suffix('"'))
+    {
+        Some(quoted) => {
+            let mut name = String::new();
+            let mut chars = quoted.chars();
+            while let Some(c) = chars.next() {
+                name.push(if c == '\\' {
+                    chars.next().unwrap_or(c)
+                } else {
+                    c
+                });
+            }
+            name
+        }
+        None => name.to_string(),
+    };
+    (Some(name), address.1.trim())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -103,4 +381,119 @@ mod tests {
         let email: Result<EmailAddress, _> = "invalid-email".parse();
         assert!(email.is_err());
     }
+
+    #[test]
+    fn test_email_address_from_str_rfc() {
+        let email: EmailAddress = "First.Last+tag@Custom.COM".parse().unwrap();
+        assert_eq!(email.name, "First.Last+tag");
+        assert_eq!(email.domain, Domain::Custom("custom.com".into()));
+
+        let email: EmailAddress = r#""a@b \"c\""@custom.com"#.parse().unwrap();
+        assert_eq!(email.name, r#""a@b \"c\"""#);
+        assert_eq!(email.domain, Domain::Custom("custom.com".into()));
+
+        let email: EmailAddress = "test@TerribleCoffee.org".parse().unwrap();
+        assert_eq!(email.domain, Domain::TerribleCoffeeOrg);
+    }
+
+    #[test]
+    fn test_email_address_from_str_errors() {
+        let parse = |s: &str| s.parse::<EmailAddress>().unwrap_err();
+        assert!(matches!(
+            parse("@custom.com"),
+            EmailAddressError::EmptyLocalPart
+        ));
+        assert!(matches!(
+            parse(&format!("{}@custom.com", "a".repeat(65))),
+            EmailAddressError::LocalPartTooLong(65)
+        ));
+        assert!(matches!(
+            parse(&format!("a@{}.com", "b.".repeat(126))),
+            EmailAddressError::TooLong(258)
+        ));
+        for invalid in [
+            "a@b@custom.com",
+            ".a@custom.com",
+            "a..b@custom.com",
+            "a b@custom.com",
+        ] {
+            assert!(matches!(
+                parse(invalid),
+                EmailAddressError::InvalidLocalPart(_)
+            ));
+        }
+        assert!(matches!(
+            parse("a@custom"),
+            EmailAddressError::InvalidDomain(_)
+        ));
+        assert!(matches!(
+            parse(" a@custom.com"),
+            EmailAddressError::InvalidLocalPart(_)
+        ));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() {
+        let message = Message {
+            id: "1".to_string(),
+            from: "Sender <sender@example.com>".parse().unwrap(),
+            to: vec!["test@underseagolf.com".parse().unwrap()],
+            cc: Vec::new(),
+            reply_to: Vec::new(),
+            subject: "Hello".to_string(),
+            body: "World".to_string(),
+            date: DateTime::from_timestamp(1_700_000_000, 0),
+        };
+        let json = serde_json::to_string(&message).unwrap();
+        assert_eq!(message, serde_json::from_str(&json).unwrap());
+
+        let address = EmailAddress::new("test", Domain::UnderseaGolfCom);
+        let json = serde_json::to_string(&address).unwrap();
+        assert_eq!(json, "\"test@underseagolf.com\"");
+        assert_eq!(address, serde_json::from_str(&json).unwrap());
+        assert!(serde_json::from_str::<EmailAddress>("\"invalid\"").is_err());
+    }
+
+    #[test]
+    fn test_mailbox_from_str() {
+        let mailbox: Mailbox = "Jane Doe <jane@example.com>".parse().unwrap();
+        assert_eq!(Some("Jane Doe"), mailbox.name.as_deref());
+        assert_eq!("Jane Doe <jane@example.com>", mailbox.to_string());
+
+        let mailbox: Mailbox = r#""Jane \"JD\" Doe" <jane@example.com>"#.parse().unwrap();
+        assert_eq!(Some(r#"Jane "JD" Doe"#), mailbox.name.as_deref());
+        assert_eq!(
+            r#""Jane \"JD\" Doe" <jane@example.com>"#,
+            mailbox.to_string()
+        );
+
+        let mailbox: Mailbox = "<jane@example.com>".parse().unwrap();
+        assert_eq!(None, mailbox.name);
+        assert!("Jane Doe".parse::<Mailbox>().is_err());
+        assert!("Jane <jane@invalid>".parse::<Mailbox>().is_err());
+    }
+
+    #[test]
+    fn test_mailbox_from_header() {
+        let mailbox = Mailbox::from_header("Jane <Jane@Localhost>");
+        assert_eq!("Jane <Jane@localhost>", mailbox.to_string());
+
+        let mailbox = Mailbox::from_header("Mail Delivery System");
+        assert_eq!(Some("Mail Delivery System"), mailbox.name.as_deref());
+        assert_eq!("unknown@invalid", mailbox.address.to_string());
+
+        let mailboxes =
+            Mailbox::list_from_header(r#""Doe, Jane" <jane@example.com>, john@example.com,"#);
+        assert_eq!(
+            vec![
+                r#""Doe, Jane" <jane@example.com>"#.to_string(),
+                "john@example.com".to_string()
+            ],
+            mailboxes
+                .iter()
+                .map(ToString::to_string)
+                .collect::<Vec<_>>()
+        );
+    }
 }
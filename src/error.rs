@@ -1,6 +1,103 @@
-use crate::client::StatusCode;
+use std::fmt::{self, Display, Formatter};
+use std::time::Duration;
 
+use crate::client::{Response, StatusCode, Url};
+
+use crate::capabilities::Capability;
 use crate::provider::ProviderType;
+use crate::trace::redact_url;
+
+/// The maximum number of characters of a response body kept in a [`ProviderError`].
+const MAX_BODY_LENGTH: usize = 512;
+
+/// Details of a request a provider answered with an error.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProviderError {
+    /// The provider that was requested.
+    pub provider_type: ProviderType,
+    /// The URL of the request, with the values of query parameters redacted.
+    pub url: String,
+    /// The status code of the response.
+    pub status: StatusCode,
+    /// The start of the response body.
+    pub body: String,
+    /// How long to wait before retrying, from the `Retry-After` header of the response.
+    pub retry_after: Option<Duration>,
+}
+
+impl ProviderError {
+    /// Reads the details from an error response.
+    pub(crate) async fn from_response(provider_type: ProviderType, response: Response) -> Self {
+        let url = response.url().clone();
+        let status = response.status();
+        // Only the delay in seconds is supported, not the HTTP date form of the header.
+        let retry_after = response
+            .headers()
+            .get("Retry-After")
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.trim().parse().ok())
+            .map(Duration::from_secs);
+        let body = response.text().await.unwrap_or_default();
+        Self {
+            retry_after,
+            ..Self::new(provider_type, &url, status, body)
+        }
+    }
+
+    fn new(provider_type: ProviderType, url: &Url, status: StatusCode, body: String) -> Self {
+        let body = match body.char_indices().nth(MAX_BODY_LENGTH) {
+            Some((end, _)) => format!("{}...", &body[..end]),
+            None => body,
+        };
+        Self {
+            provider_type,
+            // Some providers pass tokens as query parameters, which must not end up in logs.
+            url: redact_url(url),
+            status,
+            body,
+            retry_after: None,
+        }
+    }
+
+    /// Returns whether the provider rejected the request because of too many requests.
+    pub fn is_rate_limited(&self) -> bool {
+        self.status == StatusCode::TOO_MANY_REQUESTS
+    }
+
+    /// Returns whether the provider rejected the credentials of the inbox.
+    pub fn is_auth_error(&self) -> bool {
+        self.status == StatusCode::UNAUTHORIZED || self.status == StatusCode::FORBIDDEN
+    }
+
+    /// Returns whether the request may succeed when sent again later.
+    pub fn is_retryable(&self) -> bool {
+        is_retryable_status(self.status)
+    }
+}
+
+impl Display for ProviderError {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} answered {} with {}",
+            self.provider_type, self.url, self.status
+        )?;
+        if !self.body.is_empty() {
+            write!(f, ": {}", self.body)?;
+        }
+        Ok(())
+    }
+}
+
+fn is_retryable_status(status: StatusCode) -> bool {
+    status.is_server_error()
+        || status == StatusCode::TOO_MANY_REQUESTS
+        || status == StatusCode::REQUEST_TIMEOUT
+}
+
+fn is_retryable_request_error(error: &crate::client::Error) -> bool {
+    error.is_timeout() || error.is_connect()
+}
 
 /// Represents an error that can occur when creating a temporary email inbox.
 #[derive(Debug, thiserror::Error)]
@@ -11,6 +108,9 @@ pub enum InboxCreationError {
     /// A generic error that occurred when creating an inbox.
     #[error("Cannot create inbox: {0}")]
     CreationError(String),
+    /// The provider answered with a response that could not be understood.
+    #[error("Cannot create inbox, {0} sent an unexpected response: {1}")]
+    UnexpectedResponse(ProviderType, String),
     #[error("Provider not implemented")]
     ProviderNotImplemented,
     #[error("Provider does not support specifying a domain")]
@@ -25,8 +125,80 @@ pub enum InboxCreationError {
     InvalidName(String),
     #[error("An invalid email address was returned: {0}")]
     InvalidEmailAddress(#[from] EmailAddressError),
-    #[error("Rate limited, try again later")]
-    RateLimited,
+    /// The provider rejected the request because of too many requests.
+    #[error("Rate limited, try again later: {0}")]
+    RateLimited(ProviderError),
+    #[error("Provider {0} does not support {1}")]
+    CapabilityNotSupported(ProviderType, Capability),
+    #[error("Could not find a provider supporting the required capabilities")]
+    NoProviderWithCapabilities,
+    /// The inbox pool stopped creating inboxes.
+    #[error("Inbox pool is shut down")]
+    PoolShutDown,
+    /// The provider answered a request with an error.
+    #[error("{0}")]
+    ProviderError(ProviderError),
+}
+
+impl InboxCreationError {
+    /// Returns the error for a response with an error status.
+    pub(crate) async fn from_response(provider_type: ProviderType, response: Response) -> Self {
+        let error = ProviderError::from_response(provider_type, response).await;
+        if error.is_rate_limited() {
+            return InboxCreationError::RateLimited(error);
+        }
+        InboxCreationError::ProviderError(error)
+    }
+
+    /// Returns details of the failed request, if the provider answered with an error.
+    pub fn provider_error(&self) -> Option<&ProviderError> {
+        match self {
+            InboxCreationError::RateLimited(error) | InboxCreationError::ProviderError(error) => {
+                Some(error)
+            }
+            _ => None,
+        }
+    }
+
+    /// Returns the provider that caused the error, if it is known.
+    pub fn provider_type(&self) -> Option<ProviderType> {
+        match self {
+            InboxCreationError::UnexpectedResponse(provider_type, _)
+            | InboxCreationError::InvalidDomainForProvider(_, provider_type)
+            | InboxCreationError::CapabilityNotSupported(provider_type, _) => Some(*provider_type),
+            _ => self.provider_error().map(|error| error.provider_type),
+        }
+    }
+
+    /// Returns how long the provider asked to wait before retrying, if it did.
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.provider_error().and_then(|error| error.retry_after)
+    }
+
+    /// Returns whether the provider rejected the request because of too many requests.
+    pub fn is_rate_limited(&self) -> bool {
+        self.provider_error()
+            .is_some_and(ProviderError::is_rate_limited)
+    }
+
+    /// Returns whether the provider rejected the request as unauthorized.
+    pub fn is_auth_error(&self) -> bool {
+        self.provider_error()
+            .is_some_and(ProviderError::is_auth_error)
+    }
+
+    /// Returns whether creating the inbox may succeed when tried again later.
+    ///
+    /// This is the case for timeouts, connection errors, rate limits and server errors.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            InboxCreationError::RquestError(error) => is_retryable_request_error(error),
+            InboxCreationError::RateLimited(error) | InboxCreationError::ProviderError(error) => {
+                error.is_retryable()
+            }
+            _ => false,
+        }
+    }
 }
 
 /// Represents an error that can occur when fetching a message from an inbox.
@@ -40,12 +212,242 @@ pub enum MessageFetcherError {
     /// A generic error that occurred when fetching a message.
     #[error("Failed to fetch message: {0}")]
     FetchError(String),
+    /// The provider answered with a response that could not be understood.
+    #[error("Failed to fetch message, {0} sent an unexpected response: {1}")]
+    UnexpectedResponse(ProviderType, String),
+    #[error("Provider does not support deleting inboxes")]
+    DeletionNotSupported,
+    #[error("Message not found: {0}")]
+    MessageNotFound(String),
+    /// The provider rejected the request because of too many requests.
+    #[error("Rate limited, try again later: {0}")]
+    RateLimited(ProviderError),
+    /// The provider rejected the credentials of the inbox, for example because it expired.
+    #[error("Not authorized: {0}")]
+    AuthError(ProviderError),
+    /// The provider answered a request with an error.
+    #[error("{0}")]
+    ProviderError(ProviderError),
+    /// The session of the inbox expired without the provider answering with an error status.
+    #[error("The session of the inbox expired")]
+    SessionExpired,
+    /// The session of the inbox expired, and signing in again failed.
+    #[error("The session expired and could not be refreshed: {0}")]
+    SessionRefreshFailed(Box<InboxCreationError>),
+    /// The provider deleted the inbox because its lifetime ended.
+    #[error("The inbox expired")]
+    InboxExpired,
+    /// The provider does not allow extending the lifetime of inboxes.
+    #[error("Provider does not support keeping inboxes alive")]
+    KeepAliveNotSupported,
+}
+
+impl MessageFetcherError {
+    /// Returns the error for a response with an error status.
+    pub(crate) async fn from_response(provider_type: ProviderType, response: Response) -> Self {
+        let error = ProviderError::from_response(provider_type, response).await;
+        if error.is_rate_limited() {
+            MessageFetcherError::RateLimited(error)
+        } else if error.is_auth_error() {
+            MessageFetcherError::AuthError(error)
+        } else {
+            MessageFetcherError::ProviderError(error)
+        }
+    }
+
+    /// Returns details of the failed request, if the provider answered with an error.
+    pub fn provider_error(&self) -> Option<&ProviderError> {
+        match self {
+            MessageFetcherError::RateLimited(error)
+            | MessageFetcherError::AuthError(error)
+            | MessageFetcherError::ProviderError(error) => Some(error),
+            _ => None,
+        }
+    }
+
+    /// Returns the provider that caused the error, if it is known.
+    pub fn provider_type(&self) -> Option<ProviderType> {
+        match self {
+            MessageFetcherError::UnexpectedResponse(provider_type, _) => Some(*provider_type),
+            _ => self.provider_error().map(|error| error.provider_type),
+        }
+    }
+
+    /// Returns the status code of the failed request, if the provider answered with an error.
+    pub fn status(&self) -> Option<StatusCode> {
+        match self {
+            MessageFetcherError::InvalidResponseStatus(status) => Some(*status),
+            _ => self.provider_error().map(|error| error.status),
+        }
+    }
+
+    /// Returns how long the provider asked to wait before retrying, if it did.
+    pub fn retry_after(&self) -> Option<Duration> {
+        self.provider_error().and_then(|error| error.retry_after)
+    }
+
+    /// Returns whether the provider rejected the request because of too many requests.
+    pub fn is_rate_limited(&self) -> bool {
+        self.status() == Some(StatusCode::TOO_MANY_REQUESTS)
+    }
+
+    /// Returns whether the provider rejected the credentials of the inbox.
+    pub fn is_auth_error(&self) -> bool {
+        match self {
+            MessageFetcherError::SessionExpired | MessageFetcherError::SessionRefreshFailed(_) => {
+                true
+            }
+            _ => self.status().is_some_and(|status| {
+                status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN
+            }),
+        }
+    }
+
+    /// Returns whether the request may succeed when sent again later.
+    ///
+    /// This is the case for timeouts, connection errors, rate limits and server errors.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            MessageFetcherError::RquestError(error) => is_retryable_request_error(error),
+            _ => self.status().is_some_and(is_retryable_status),
+        }
+    }
+}
+
+/// Represents an error that can occur when exporting an inbox with [`crate::Inbox::export`].
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum ExportError {
+    #[error("Failed to fetch messages: {0}")]
+    Fetch(#[from] MessageFetcherError),
+    #[error("Failed to write export: {0}")]
+    Io(#[from] std::io::Error),
 }
 
 /// Represents an error that can occur when parsing an email address.
 #[derive(Debug, thiserror::Error)]
 #[non_exhaustive]
 pub enum EmailAddressError {
+    /// The address does not contain an `@`.
     #[error("Invalid email address: {0}")]
     InvalidEmailAddress(String),
+    #[error("The part before the @ is empty")]
+    EmptyLocalPart,
+    #[error("The part before the @ is {0} characters long, at most 64 are allowed")]
+    LocalPartTooLong(usize),
+    #[error("Invalid part before the @: {0}")]
+    InvalidLocalPart(String),
+    #[error("The address is {0} characters long, at most 254 are allowed")]
+    TooLong(usize),
+    #[error("Invalid domain: {0}")]
+    InvalidDomain(#[from] DomainError),
+}
+
+/// Represents an error that can occur when parsing a domain.
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[non_exhaustive]
+pub enum DomainError {
+    #[error("Unknown domain: {0}")]
+    UnknownDomain(String),
+    #[error("Invalid domain: {0}")]
+    InvalidDomain(String),
+}
+
+/// Represents an error that can occur when parsing a [`ProviderType`].
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+#[non_exhaustive]
+pub enum ProviderTypeError {
+    #[error("Unknown provider: {0}")]
+    UnknownProvider(String),
+    /// The provider exists, but needs a feature flag that is not enabled.
+    #[error("Provider {0} needs the {1} feature")]
+    FeatureNotEnabled(String, &'static str),
+}
+
+/// Represents an error that can occur when loading a [`crate::DomainCatalog`].
+#[derive(Debug, thiserror::Error)]
+#[non_exhaustive]
+pub enum DomainCatalogError {
+    #[error("Failed to read catalog: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Failed to parse catalog: {0}")]
+    Parse(#[from] serde_json::Error),
+    #[error("Invalid domain in catalog: {0}")]
+    InvalidDomain(#[from] DomainError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn provider_error(status: StatusCode) -> ProviderError {
+        ProviderError {
+            provider_type: ProviderType::MailTm,
+            url: "https://api.mail.tm/messages".to_string(),
+            status,
+            body: String::new(),
+            retry_after: None,
+        }
+    }
+
+    #[test]
+    fn test_message_fetcher_error_classification() {
+        let error = MessageFetcherError::RateLimited(provider_error(StatusCode::TOO_MANY_REQUESTS));
+        assert!(error.is_rate_limited() && error.is_retryable() && !error.is_auth_error());
+
+        let error = MessageFetcherError::AuthError(provider_error(StatusCode::UNAUTHORIZED));
+        assert!(error.is_auth_error() && !error.is_retryable());
+
+        let error = MessageFetcherError::SessionExpired;
+        assert!(error.is_auth_error() && error.status().is_none());
+
+        let error = MessageFetcherError::InvalidResponseStatus(StatusCode::BAD_GATEWAY);
+        assert!(error.is_retryable() && !error.is_rate_limited());
+
+        let error = MessageFetcherError::MessageNotFound("1".to_string());
+        assert!(!error.is_retryable() && error.status().is_none());
+
+        let error = MessageFetcherError::UnexpectedResponse(
+            ProviderType::FakeMailNet,
+            "Failed to parse JSON".to_string(),
+        );
+        assert_eq!(Some(ProviderType::FakeMailNet), error.provider_type());
+        assert!(!error.is_retryable());
+    }
+
+    #[test]
+    fn test_inbox_creation_error_classification() {
+        let error = InboxCreationError::RateLimited(ProviderError {
+            retry_after: Some(Duration::from_secs(30)),
+            ..provider_error(StatusCode::TOO_MANY_REQUESTS)
+        });
+        assert!(error.is_retryable() && error.is_rate_limited());
+        assert_eq!(Some(Duration::from_secs(30)), error.retry_after());
+        assert_eq!(Some(ProviderType::MailTm), error.provider_type());
+        assert!(!InboxCreationError::NameTaken("a".to_string()).is_retryable());
+
+        let error = InboxCreationError::ProviderError(provider_error(StatusCode::FORBIDDEN));
+        assert!(error.is_auth_error() && !error.is_retryable());
+        assert_eq!(
+            error.to_string(),
+            "Mail.tm answered https://api.mail.tm/messages with 403 Forbidden"
+        );
+    }
+
+    #[test]
+    fn test_provider_error_redacts_url() {
+        let url = Url::parse("https://api.tempmail.lol/v2/inbox?token=secret").unwrap();
+        let error = ProviderError::new(
+            ProviderType::TempMailLol,
+            &url,
+            StatusCode::BAD_GATEWAY,
+            String::new(),
+        );
+        let error = MessageFetcherError::ProviderError(error).to_string();
+        assert!(!error.contains("secret"));
+        assert_eq!(
+            error,
+            "TempMail.lol answered https://api.tempmail.lol/v2/inbox?token=redacted with 502 Bad Gateway"
+        );
+    }
 }
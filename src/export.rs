@@ -0,0 +1,271 @@
+use std::fmt::{self, Display, Formatter};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::process;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use chrono::{DateTime, Utc};
+
+use crate::{EmailAddress, Mailbox, Message};
+
+/// The maximum length of an encoded word in a header, from RFC 2047.
+const MAX_ENCODED_WORD_LENGTH: usize = 75;
+
+/// The format of an inbox export, see [`crate::Inbox::export`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ExportFormat {
+    /// A single file in the mboxrd format, with all messages appended to it.
+    Mbox,
+    /// A Maildir directory, with one file per message in its `new` subdirectory.
+    Maildir,
+}
+
+impl Display for ExportFormat {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            ExportFormat::Mbox => write!(f, "mbox"),
+            ExportFormat::Maildir => write!(f, "Maildir"),
+        }
+    }
+}
+
+/// Writes `messages` with their sources to `path` in `format`.
+pub(crate) fn write(
+    format: ExportFormat,
+    path: &Path,
+    messages: &[(Message, String)],
+) -> io::Result<()> {
+    match format {
+        ExportFormat::Mbox => {
+            let mut writer = BufWriter::new(File::create(path)?);
+            write_mbox(&mut writer, messages)?;
+            writer.flush()
+        }
+        ExportFormat::Maildir => write_maildir(path, messages),
+    }
+}
+
+/// Returns the source of `message` in the RFC 5322 format, with `\n` line endings.
+///
+/// The raw `source` is used if the provider offers it, otherwise the headers are synthesized from
+/// the fields of the message.
+pub(crate) fn source(message: &Message, source: Option<&str>, recipient: &EmailAddress) -> String {
+    let mut source = match source {
+        Some(source) => source.replace("\r\n", "\n"),
+        None => synthesize(message, recipient),
+    };
+    if !source.ends_with('\n') {
+        source.push('\n');
+    }
+    source
+}
+
+fn synthesize(message: &Message, recipient: &EmailAddress) -> String {
+    let content_type = if message.body.trim_start().starts_with('<') {
+        "text/html"
+    } else {
+        "text/plain"
+    };
+    let message_id: String = message
+        .id
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || "-._".contains(*c))
+        .collect();
+
+    let to = if message.to.is_empty() {
+        mailbox_list(&[recipient.clone().into()])
+    } else {
+        mailbox_list(&message.to)
+    };
+
+    let mut source = String::new();
+    source += &format!("From: {}\n", mailbox(&message.from));
+    source += &format!("To: {}\n", to);
+    if !message.cc.is_empty() {
+        source += &format!("Cc: {}\n", mailbox_list(&message.cc));
+    }
+    if !message.reply_to.is_empty() {
+        source += &format!("Reply-To: {}\n", mailbox_list(&message.reply_to));
+    }
+    source += &format!("Subject: {}\n", encode_header(&message.subject));
+    if let Some(date) = message.date {
+        source += &format!("Date: {}\n", date.to_rfc2822());
+    }
+    source += &format!("Message-ID: <{}@{}>\n", message_id, recipient.domain);
+    source += "MIME-Version: 1.0\n";
+    source += &format!("Content-Type: {}; charset=utf-8\n", content_type);
+    source += "Content-Transfer-Encoding: 8bit\n";
+    source += "\n";
+    source += &message.body.replace("\r\n", "\n");
+    source
+}
+
+/// Formats a mailbox for a header, encoding a display name that is not plain ASCII.
+fn mailbox(mailbox: &Mailbox) -> String {
+    match &mailbox.name {
+        Some(name) if !name.is_ascii() => {
+            format!("{} <{}>", encode_header(name), mailbox.address)
+        }
+        _ => header_value(&mailbox.to_string()),
+    }
+}
+
+fn mailbox_list(mailboxes: &[Mailbox]) -> String {
+    mailboxes.iter().map(mailbox).collect::<Vec<_>>().join(", ")
+}
+
+/// Removes line breaks, which would start a new header.
+fn header_value(value: &str) -> String {
+    value.replace(['\r', '\n'], " ")
+}
+
+/// Encodes a header value with RFC 2047 encoded words if it is not plain ASCII.
+fn encode_header(value: &str) -> String {
+    let value = header_value(value);
+    if value.is_ascii() {
+        return value;
+    }
+
+    const PREFIX: &str = "=?UTF-8?Q?";
+    const SUFFIX: &str = "?=";
+    let mut words = Vec::new();
+    let mut word = String::new();
+    for c in value.chars() {
+        let mut encoded = String::new();
+        let mut buffer = [0; 4];
+        for byte in c.encode_utf8(&mut buffer).bytes() {
+            match byte {
+                b' ' => encoded.push('_'),
+                b if b.is_ascii_alphanumeric() => encoded.push(b as char),
+                b => encoded += &format!("={:02X}", b),
+            }
+        }
+        // Characters are never split between words, as required by RFC 2047.
+        if PREFIX.len() + word.len() + encoded.len() + SUFFIX.len() > MAX_ENCODED_WORD_LENGTH {
+            words.push(format!("{}{}{}", PREFIX, word, SUFFIX));
+            word.clear();
+        }
+        word += &encoded;
+    }
+    words.push(format!("{}{}{}", PREFIX, word, SUFFIX));
+    words.join("\n ")
+}
+
+fn write_mbox(writer: &mut impl Write, messages: &[(Message, String)]) -> io::Result<()> {
+    for (message, source) in messages {
+        let date = message
+            .date
+            .unwrap_or_else(|| DateTime::<Utc>::from(SystemTime::now()));
+        writeln!(
+            writer,
+            "From {} {}",
+            message.from.address,
+            date.format("%a %b %e %H:%M:%S %Y")
+        )?;
+        for line in source.lines() {
+            // mboxrd quotes lines starting with any number of `>` followed by `From `, so
+            // readers can restore them by removing one `>`.
+            if line.trim_start_matches('>').starts_with("From ") {
+                writer.write_all(b">")?;
+            }
+            writeln!(writer, "{}", line)?;
+        }
+        writeln!(writer)?;
+    }
+    Ok(())
+}
+
+fn write_maildir(dir: &Path, messages: &[(Message, String)]) -> io::Result<()> {
+    for subdir in ["tmp", "new", "cur"] {
+        fs::create_dir_all(dir.join(subdir))?;
+    }
+    let time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    for (index, (_, source)) in messages.iter().enumerate() {
+        let name = format!(
+            "{}.M{}P{}Q{}.ephemeral_email",
+            time.as_secs(),
+            time.subsec_micros(),
+            process::id(),
+            index
+        );
+        // Messages are written to `tmp` first, so readers never see partial files.
+        let tmp_path = dir.join("tmp").join(&name);
+        fs::write(&tmp_path, source)?;
+        fs::rename(&tmp_path, dir.join("new").join(&name))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Domain;
+
+    fn message(subject: &str, body: &str) -> Message {
+        Message {
+            id: "abc".to_string(),
+            from: "Sender <sender@example.com>".parse().unwrap(),
+            to: Vec::new(),
+            cc: Vec::new(),
+            reply_to: Vec::new(),
+            subject: subject.to_string(),
+            body: body.to_string(),
+            date: DateTime::from_timestamp(1_700_000_000, 0),
+        }
+    }
+
+    fn recipient() -> EmailAddress {
+        EmailAddress::new("test", Domain::Custom("example.org".to_string()))
+    }
+
+    #[test]
+    fn test_synthesized_headers() {
+        let source = source(&message("Hello", "<p>Hi</p>"), None, &recipient());
+        assert_eq!(
+            source,
+            "From: Sender <sender@example.com>\n\
+             To: test@example.org\n\
+             Subject: Hello\n\
+             Date: Tue, 14 Nov 2023 22:13:20 +0000\n\
+             Message-ID: <abc@example.org>\n\
+             MIME-Version: 1.0\n\
+             Content-Type: text/html; charset=utf-8\n\
+             Content-Transfer-Encoding: 8bit\n\
+             \n\
+             <p>Hi</p>\n"
+        );
+    }
+
+    #[test]
+    fn test_encoded_mailbox() {
+        let mut message = message("Hello", "Hi");
+        message.from.name = Some("Jürgen".to_string());
+        let source = source(&message, None, &recipient());
+        assert!(source.starts_with("From: =?UTF-8?Q?J=C3=BCrgen?= <sender@example.com>\n"));
+    }
+
+    #[test]
+    fn test_encode_header() {
+        assert_eq!(encode_header("Plain\r\nsubject"), "Plain  subject");
+        assert_eq!(encode_header("Grüße"), "=?UTF-8?Q?Gr=C3=BC=C3=9Fe?=");
+        let encoded = encode_header(&"ä".repeat(40));
+        assert!(encoded
+            .split("\n ")
+            .all(|word| word.len() <= MAX_ENCODED_WORD_LENGTH));
+    }
+
+    #[test]
+    fn test_mbox_quotes_from_lines() {
+        let message = message("Hello", "Hi\nFrom here\n>From there");
+        let source = source(&message, None, &recipient());
+        let mut mbox = Vec::new();
+        write_mbox(&mut mbox, &[(message, source)]).unwrap();
+        let mbox = String::from_utf8(mbox).unwrap();
+        assert!(mbox.starts_with("From sender@example.com Tue Nov 14 22:13:20 2023\n"));
+        assert!(mbox.ends_with("Hi\n>From here\n>>From there\n\n"));
+    }
+}
@@ -0,0 +1,189 @@
+use chrono::{DateTime, Utc};
+use regex::Regex;
+
+use crate::{Mailbox, Message, MessageSummary};
+
+/// A builder for selecting messages by sender, subject, body or time.
+///
+/// All conditions that are set must match. Text conditions ignore case. Messages without a date,
+/// which some providers do not report, are not excluded by [`MessageFilter::since`] and
+/// [`MessageFilter::until`].
+///
+/// Use it with [`crate::Inbox::get_messages_matching`], [`crate::Inbox::watch_matching`] or
+/// [`crate::Inbox::wait_for_message`]. Where a provider supports it, the filter is applied by the
+/// provider, so fewer messages are downloaded.
+///
+/// # Example
+/// ```no_run
+/// use ephemeral_email::{Inbox, MessageFilter, Regex};
+///
+/// async fn example(inbox: Inbox) {
+///     let filter = MessageFilter::new()
+///         .from("noreply@example.com")
+///         .subject_regex(Regex::new(r"^Your code is \d{6}$").unwrap());
+///     for message in inbox.get_messages_matching(&filter).await.unwrap() {
+///         println!("Code: {}", message.subject);
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MessageFilter {
+    pub(crate) from: Option<String>,
+    pub(crate) subject: Option<String>,
+    subject_regex: Option<Regex>,
+    body: Option<String>,
+    body_regex: Option<Regex>,
+    pub(crate) since: Option<DateTime<Utc>>,
+    pub(crate) until: Option<DateTime<Utc>>,
+}
+
+impl MessageFilter {
+    /// Creates a filter matching all messages.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Only matches messages whose sender address or name contains `from`.
+    pub fn from(mut self, from: impl Into<String>) -> Self {
+        self.from = Some(from.into());
+        self
+    }
+
+    /// Only matches messages whose subject contains `subject`.
+    pub fn subject(mut self, subject: impl Into<String>) -> Self {
+        self.subject = Some(subject.into());
+        self
+    }
+
+    /// Only matches messages whose subject matches `regex`.
+    pub fn subject_regex(mut self, regex: Regex) -> Self {
+        self.subject_regex = Some(regex);
+        self
+    }
+
+    /// Only matches messages whose body contains `body`.
+    pub fn body(mut self, body: impl Into<String>) -> Self {
+        self.body = Some(body.into());
+        self
+    }
+
+    /// Only matches messages whose body matches `regex`.
+    pub fn body_regex(mut self, regex: Regex) -> Self {
+        self.body_regex = Some(regex);
+        self
+    }
+
+    /// Only matches messages received at or after `since`.
+    pub fn since(mut self, since: DateTime<Utc>) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    /// Only matches messages received at or before `until`.
+    pub fn until(mut self, until: DateTime<Utc>) -> Self {
+        self.until = Some(until);
+        self
+    }
+
+    /// Returns whether `message` matches all conditions of the filter.
+    pub fn matches(&self, message: &Message) -> bool {
+        self.matches_headers(&message.from, &message.subject, message.date)
+            && contains(&message.body, self.body.as_deref())
+            && self
+                .body_regex
+                .as_ref()
+                .is_none_or(|regex| regex.is_match(&message.body))
+    }
+
+    /// Returns whether a message with this summary may match the filter.
+    ///
+    /// Conditions on the body are not checked, as the summary does not contain it.
+    pub fn may_match(&self, summary: &MessageSummary) -> bool {
+        self.matches_headers(&summary.from, &summary.subject, summary.date)
+    }
+
+    fn matches_headers(&self, from: &Mailbox, subject: &str, date: Option<DateTime<Utc>>) -> bool {
+        (contains(&from.address.to_string(), self.from.as_deref())
+            || from
+                .name
+                .as_ref()
+                .is_some_and(|name| contains(name, self.from.as_deref())))
+            && contains(subject, self.subject.as_deref())
+            && self
+                .subject_regex
+                .as_ref()
+                .is_none_or(|regex| regex.is_match(subject))
+            && date.is_none_or(|date| {
+                self.since.is_none_or(|since| date >= since)
+                    && self.until.is_none_or(|until| date <= until)
+            })
+    }
+}
+
+/// Returns whether `haystack` contains `needle`, ignoring case. A missing needle always matches.
+fn contains(haystack: &str, needle: Option<&str>) -> bool {
+    needle.is_none_or(|needle| haystack.to_lowercase().contains(&needle.to_lowercase()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message() -> Message {
+        Message {
+            id: "1".to_string(),
+            from: "Example Shop <noreply@example.com>".parse().unwrap(),
+            to: Vec::new(),
+            cc: Vec::new(),
+            reply_to: Vec::new(),
+            subject: "Your code is 123456".to_string(),
+            body: "Enter 123456 to sign in.".to_string(),
+            date: DateTime::from_timestamp(1_700_000_000, 0),
+        }
+    }
+
+    #[test]
+    fn test_matches() {
+        let message = message();
+        assert!(MessageFilter::new().matches(&message));
+        assert!(MessageFilter::new().from("NOREPLY@").matches(&message));
+        assert!(MessageFilter::new().from("shop").matches(&message));
+        assert!(!MessageFilter::new().from("other").matches(&message));
+        assert!(MessageFilter::new()
+            .subject_regex(Regex::new(r"\d{6}$").unwrap())
+            .body("sign in")
+            .matches(&message));
+        assert!(!MessageFilter::new()
+            .body_regex(Regex::new(r"^\d+$").unwrap())
+            .matches(&message));
+    }
+
+    #[test]
+    fn test_time_window() {
+        let message = message();
+        let date = message.date.unwrap();
+        assert!(MessageFilter::new()
+            .since(date)
+            .until(date)
+            .matches(&message));
+        assert!(!MessageFilter::new()
+            .since(date + chrono::Duration::seconds(1))
+            .matches(&message));
+
+        let undated = Message {
+            date: None,
+            ..message
+        };
+        assert!(MessageFilter::new()
+            .until(date - chrono::Duration::seconds(1))
+            .matches(&undated));
+    }
+
+    #[test]
+    fn test_may_match_ignores_body() {
+        let filter = MessageFilter::new().subject("code").body("missing");
+        let summary = MessageSummary::from(&message());
+        assert!(filter.may_match(&summary));
+        assert!(!filter.matches(&message()));
+    }
+}
@@ -0,0 +1,472 @@
+//! Forwarding of received messages to a webhook.
+//!
+//! A [`Forwarder`] polls one or more inboxes and sends every new message as a JSON `POST` request
+//! to a configured URL. This lets services that are not written in Rust react to incoming mail,
+//! for example a test harness running a local HTTP listener.
+//!
+//! This module is gated behind the `webhook` feature flag, which enables the `serde` feature.
+//!
+//! # Payload
+//!
+//! Each request has a JSON body with the address of the inbox, the [`crate::ProviderType::id`] of its
+//! provider and the message, serialized like with the `serde` feature:
+//!
+//! ```json
+//! {
+//!   "inbox": "name@example.com",
+//!   "provider": "mail_tm",
+//!   "message": {
+//!     "id": "1",
+//!     "from": {"name": "Sender", "address": "sender@example.com"},
+//!     "to": [{"name": null, "address": "name@example.com"}],
+//!     "cc": [],
+//!     "reply_to": [],
+//!     "subject": "Hello",
+//!     "body": "Hello World",
+//!     "date": "2025-01-01T12:00:00Z"
+//!   }
+//! }
+//! ```
+//!
+//! If a secret is set, the request carries an `X-Ephemeral-Email-Signature` header containing
+//! `sha256=` followed by the hex encoded HMAC-SHA256 of the body.
+//!
+//! # Example
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use ephemeral_email::forward::Forwarder;
+//! use ephemeral_email::TempMail;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let inbox = TempMail::new().create_inbox().await.unwrap();
+//!     Forwarder::new("http://localhost:8080/mail")
+//!         .secret("my-secret")
+//!         .poll_interval(Duration::from_secs(5))
+//!         .dead_letter_log("undelivered.jsonl")
+//!         .inbox(inbox)
+//!         .run()
+//!         .await;
+//! }
+//! ```
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use hmac::{Hmac, Mac};
+use serde_json::json;
+use sha2::Sha256;
+
+use crate::client::Client;
+use crate::trace::event;
+use crate::{Inbox, Message, MessageFetcherError};
+
+/// The header containing the signature of the request body.
+pub const SIGNATURE_HEADER: &str = "X-Ephemeral-Email-Signature";
+
+/// Watches inboxes and forwards new messages to a webhook.
+///
+/// Failed deliveries are retried with an exponential backoff. Messages that still cannot be
+/// delivered are appended to the dead-letter log, if one is configured. Otherwise, and if the log
+/// cannot be written, they are reported as a `tracing` event with the `tracing` feature.
+pub struct Forwarder {
+    client: Client,
+    url: String,
+    secret: Option<Vec<u8>>,
+    max_retries: u32,
+    retry_delay: Duration,
+    poll_interval: Duration,
+    dead_letter_log: Option<PathBuf>,
+    inboxes: Vec<Inbox>,
+}
+
+impl Forwarder {
+    /// Creates a forwarder sending messages to `url`.
+    pub fn new(url: impl Into<String>) -> Self {
+        Self {
+            client: Client::new(),
+            url: url.into(),
+            secret: None,
+            max_retries: 3,
+            retry_delay: Duration::from_secs(1),
+            poll_interval: Duration::from_secs(5),
+            dead_letter_log: None,
+            inboxes: Vec::new(),
+        }
+    }
+
+    /// Sets the secret used to sign the requests with HMAC-SHA256.
+    pub fn secret(mut self, secret: impl AsRef<[u8]>) -> Self {
+        self.secret = Some(secret.as_ref().to_vec());
+        self
+    }
+
+    /// Sets how often a failed delivery is retried. Defaults to 3.
+    pub fn max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Sets the delay before the first retry, which doubles with every further retry. Defaults to 1 second.
+    pub fn retry_delay(mut self, retry_delay: Duration) -> Self {
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    /// Sets how often the inboxes are checked for new messages. Defaults to 5 seconds.
+    pub fn poll_interval(mut self, poll_interval: Duration) -> Self {
+        self.poll_interval = poll_interval;
+        self
+    }
+
+    /// Sets the file that messages are appended to as JSON lines when they cannot be delivered.
+    pub fn dead_letter_log(mut self, path: impl Into<PathBuf>) -> Self {
+        self.dead_letter_log = Some(path.into());
+        self
+    }
+
+    /// Adds an inbox to watch.
+    pub fn inbox(mut self, inbox: Inbox) -> Self {
+        self.inboxes.push(inbox);
+        self
+    }
+
+    /// Watches the inboxes and forwards new messages until the future is dropped.
+    ///
+    /// Errors that may go away, like rate limits, are retried after the poll interval. An inbox
+    /// failing with another error, for example because it expired, is no longer watched, and the
+    /// future completes once no inbox is left. Errors are reported as `tracing` events with the
+    /// `tracing` feature.
+    pub async fn run(self) {
+        let mut watched: Vec<&Inbox> = self.inboxes.iter().collect();
+        while !watched.is_empty() {
+            let mut failed = Vec::new();
+            for (index, inbox) in watched.iter().enumerate() {
+                if let Err(error) = self.forward_inbox(inbox).await {
+                    if error.is_retryable() {
+                        event!(
+                            warn,
+                            inbox = %inbox.get_email_address(),
+                            error = %error,
+                            "fetching messages failed, retrying"
+                        );
+                    } else {
+                        event!(
+                            error,
+                            inbox = %inbox.get_email_address(),
+                            error = %error,
+                            "fetching messages failed, no longer watching the inbox"
+                        );
+                        failed.push(index);
+                    }
+                }
+            }
+            for index in failed.into_iter().rev() {
+                watched.remove(index);
+            }
+            tokio::time::sleep(self.poll_interval).await;
+        }
+    }
+
+    /// Checks all inboxes once and forwards their new messages.
+    ///
+    /// Returns the number of delivered messages. If fetching the messages of an inbox fails, the
+    /// other inboxes are still checked and the first error is returned.
+    pub async fn forward_new_messages(&self) -> Result<usize, MessageFetcherError> {
+        let mut delivered = 0;
+        let mut first_error = None;
+        for inbox in &self.inboxes {
+            match self.forward_inbox(inbox).await {
+                Ok(count) => delivered += count,
+                Err(error) => {
+                    first_error.get_or_insert(error);
+                }
+            }
+        }
+        match first_error {
+            Some(error) => Err(error),
+            None => Ok(delivered),
+        }
+    }
+
+    /// Forwards the new messages of `inbox`, returning the number of delivered messages.
+    async fn forward_inbox(&self, inbox: &Inbox) -> Result<usize, MessageFetcherError> {
+        let mut delivered = 0;
+        for message in inbox.get_new_messages().await? {
+            if self.forward(inbox, &message).await {
+                delivered += 1;
+            }
+        }
+        Ok(delivered)
+    }
+
+    /// Delivers a message, writing it to the dead-letter log if all attempts fail.
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    async fn forward(&self, inbox: &Inbox, message: &Message) -> bool {
+        let payload = json!({
+            "inbox": inbox.get_email_address(),
+            "provider": inbox.get_provider_type(),
+            "message": message,
+        });
+        let body = payload.to_string();
+
+        let mut retry_delay = self.retry_delay;
+        let mut attempt = 0;
+        let error = loop {
+            let error = match self.deliver(&body).await {
+                Ok(()) => return true,
+                Err(error) => error,
+            };
+            if attempt >= self.max_retries {
+                break error;
+            }
+            attempt += 1;
+            tokio::time::sleep(retry_delay).await;
+            retry_delay *= 2;
+        };
+
+        let Some(path) = &self.dead_letter_log else {
+            event!(
+                warn,
+                message_id = %message.id,
+                error = %error,
+                "message could not be delivered and was dropped"
+            );
+            return false;
+        };
+        let entry = json!({
+            "payload": payload,
+            "error": error,
+            "attempts": attempt + 1,
+        });
+        let written = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(path)
+            .and_then(|mut file| writeln!(file, "{}", entry));
+        if let Err(write_error) = written {
+            event!(
+                error,
+                message_id = %message.id,
+                error = %error,
+                write_error = %write_error,
+                "message could not be delivered or written to the dead-letter log"
+            );
+        }
+        false
+    }
+
+    async fn deliver(&self, body: &str) -> Result<(), String> {
+        let mut request = self
+            .client
+            .post(&self.url)
+            .header("Content-Type", "application/json")
+            .body(body.to_string());
+        if let Some(secret) = &self.secret {
+            request = request.header(SIGNATURE_HEADER, sign(secret, body));
+        }
+        let response = request.send().await.map_err(|e| e.to_string())?;
+        if !response.status().is_success() {
+            return Err(format!("Invalid response status: {}", response.status()));
+        }
+        Ok(())
+    }
+}
+
+/// Returns the signature of `body`, as sent in the [`SIGNATURE_HEADER`].
+pub fn sign(secret: &[u8], body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+    mac.update(body.as_bytes());
+    format!("sha256={}", hex::encode(mac.finalize().into_bytes()))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+
+    use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+    use tokio::net::TcpListener;
+
+    use super::*;
+    use crate::provider::MessageFetcher;
+    use crate::ProviderType;
+
+    #[test]
+    fn test_sign() {
+        // Test vector 2 from RFC 4231.
+        assert_eq!(
+            sign(b"Jefe", "what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    /// A request received by the [`listen`] server: its signature header and body.
+    type Request = (Option<String>, String);
+
+    /// Starts a local HTTP server answering requests with `statuses` in turn, and `200 OK` once
+    /// they are used up.
+    ///
+    /// Returns the webhook URL and the received requests.
+    async fn listen(statuses: Vec<u16>) -> (String, Arc<Mutex<Vec<Request>>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/mail", listener.local_addr().unwrap());
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let received = requests.clone();
+        tokio::spawn(async move {
+            let mut statuses = statuses.into_iter();
+            loop {
+                let (stream, _) = listener.accept().await.unwrap();
+                let mut stream = BufReader::new(stream);
+                let mut signature = None;
+                let mut length = 0;
+                loop {
+                    let mut line = String::new();
+                    stream.read_line(&mut line).await.unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+                    if let Some((name, value)) = line.split_once(": ") {
+                        if name.eq_ignore_ascii_case(SIGNATURE_HEADER) {
+                            signature = Some(value.to_string());
+                        } else if name.eq_ignore_ascii_case("Content-Length") {
+                            length = value.parse().unwrap();
+                        }
+                    }
+                }
+                let mut body = vec![0; length];
+                stream.read_exact(&mut body).await.unwrap();
+                received
+                    .lock()
+                    .unwrap()
+                    .push((signature, String::from_utf8(body).unwrap()));
+
+                let status = statuses.next().unwrap_or(200);
+                let response = format!(
+                    "HTTP/1.1 {} Status\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                    status
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+        (url, requests)
+    }
+
+    /// A fetcher with a single message.
+    struct SingleMessageFetcher;
+
+    #[async_trait::async_trait]
+    impl MessageFetcher for SingleMessageFetcher {
+        async fn fetch_messages(&mut self) -> Result<Vec<Message>, MessageFetcherError> {
+            Ok(vec![Message {
+                id: "1".to_string(),
+                from: "sender@example.com".parse().unwrap(),
+                to: Vec::new(),
+                cc: Vec::new(),
+                reply_to: Vec::new(),
+                subject: "Hello".to_string(),
+                body: "Hello World".to_string(),
+                date: None,
+            }])
+        }
+
+        fn get_credentials(&self) -> String {
+            String::new()
+        }
+    }
+
+    fn inbox() -> Inbox {
+        Inbox::new(
+            "test@mail.tm".parse().unwrap(),
+            ProviderType::MailTm,
+            SingleMessageFetcher,
+        )
+    }
+
+    #[tokio::test]
+    async fn test_failed_delivery_is_retried() {
+        let (url, requests) = listen(vec![500]).await;
+        let forwarder = Forwarder::new(url)
+            .secret("secret")
+            .retry_delay(Duration::from_millis(1))
+            .inbox(inbox());
+        assert_eq!(1, forwarder.forward_new_messages().await.unwrap());
+        // The message was already forwarded.
+        assert_eq!(0, forwarder.forward_new_messages().await.unwrap());
+
+        let requests = requests.lock().unwrap();
+        assert_eq!(2, requests.len());
+        let (signature, body) = &requests[1];
+        assert_eq!(Some(sign(b"secret", body)), *signature);
+        let payload: serde_json::Value = serde_json::from_str(body).unwrap();
+        assert_eq!("test@mail.tm", payload["inbox"]);
+        assert_eq!("mail_tm", payload["provider"]);
+        assert_eq!("Hello", payload["message"]["subject"]);
+        assert_eq!("sender@example.com", payload["message"]["from"]["address"]);
+        assert_eq!(serde_json::json!([]), payload["message"]["cc"]);
+    }
+
+    /// A fetcher whose inbox no longer exists.
+    struct DeletedInboxFetcher;
+
+    #[async_trait::async_trait]
+    impl MessageFetcher for DeletedInboxFetcher {
+        async fn fetch_messages(&mut self) -> Result<Vec<Message>, MessageFetcherError> {
+            Err(MessageFetcherError::FetchError("inbox deleted".to_string()))
+        }
+
+        fn get_credentials(&self) -> String {
+            String::new()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_run_stops_watching_failed_inboxes() {
+        let inbox = Inbox::new(
+            "test@mail.tm".parse().unwrap(),
+            ProviderType::MailTm,
+            DeletedInboxFetcher,
+        );
+        let forwarder = Forwarder::new("http://127.0.0.1:9/mail")
+            .poll_interval(Duration::from_millis(1))
+            .inbox(inbox);
+        tokio::time::timeout(Duration::from_secs(5), forwarder.run())
+            .await
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_undelivered_message_is_dead_lettered() {
+        let (url, requests) = listen(vec![500, 502, 503]).await;
+        let path = std::env::temp_dir().join(format!(
+            "ephemeral-email-dead-letter-{}.jsonl",
+            std::process::id()
+        ));
+        let _ = std::fs::remove_file(&path);
+        let forwarder = Forwarder::new(url)
+            .max_retries(2)
+            .retry_delay(Duration::from_millis(20))
+            .dead_letter_log(&path)
+            .inbox(inbox());
+
+        let start = Instant::now();
+        assert_eq!(0, forwarder.forward_new_messages().await.unwrap());
+        // The delay doubles after the first retry.
+        assert!(start.elapsed() >= Duration::from_millis(60));
+        assert_eq!(3, requests.lock().unwrap().len());
+
+        let log = std::fs::read_to_string(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let entry: serde_json::Value = serde_json::from_str(log.trim()).unwrap();
+        assert_eq!(3, entry["attempts"]);
+        assert_eq!(
+            "Invalid response status: 503 Service Unavailable",
+            entry["error"]
+        );
+        assert_eq!("1", entry["payload"]["message"]["id"]);
+    }
+}
@@ -0,0 +1,202 @@
+use std::fmt::{self, Display, Formatter};
+use std::time::{Duration, Instant};
+
+use crate::client::{RequestBuilder, Response, StatusCode};
+use crate::trace::SendStep;
+use crate::ProviderType;
+
+/// The result of a provider health check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum HealthState {
+    /// The provider responded as expected.
+    Healthy,
+    /// The provider answered with a Cloudflare challenge. Enabling the `use-rquest` feature may help.
+    Blocked,
+    /// The provider rejected the request because of too many requests.
+    RateLimited,
+    /// The provider responded with an error status or an unexpected response.
+    Unhealthy,
+    /// The provider could not be reached.
+    Unreachable,
+}
+
+impl Display for HealthState {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        match self {
+            HealthState::Healthy => write!(f, "healthy"),
+            HealthState::Blocked => write!(f, "blocked"),
+            HealthState::RateLimited => write!(f, "rate limited"),
+            HealthState::Unhealthy => write!(f, "unhealthy"),
+            HealthState::Unreachable => write!(f, "unreachable"),
+        }
+    }
+}
+
+/// The status of a provider, as returned by [`ProviderType::health_check`].
+///
+/// # Example
+/// ```no_run
+/// use ephemeral_email::ProviderType;
+///
+/// #[tokio::main]
+/// async fn main() {
+///     for status in ProviderType::health_check_all().await {
+///         println!(
+///             "{}: {} in {:?}",
+///             status.provider_type, status.state, status.latency
+///         );
+///     }
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealthStatus {
+    /// The checked provider.
+    pub provider_type: ProviderType,
+    /// The result of the check.
+    pub state: HealthState,
+    /// The time until the response was received.
+    pub latency: Duration,
+    /// The status code of the response, if one was received.
+    pub status_code: Option<StatusCode>,
+    /// Details on why the provider is not healthy.
+    pub message: Option<String>,
+}
+
+impl HealthStatus {
+    /// Returns whether the provider responded as expected.
+    pub fn is_healthy(&self) -> bool {
+        self.state == HealthState::Healthy
+    }
+
+    /// Returns the status of a provider that could not be probed.
+    pub(crate) fn unreachable(provider_type: ProviderType, error: impl Display) -> Self {
+        Self {
+            provider_type,
+            state: HealthState::Unreachable,
+            latency: Duration::ZERO,
+            status_code: None,
+            message: Some(error.to_string()),
+        }
+    }
+}
+
+/// The time after which a probe is considered failed.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Sends a probe request and classifies the response.
+///
+/// `validate` receives the response status and body and returns whether they look as expected.
+pub(crate) async fn probe(
+    provider_type: ProviderType,
+    request: RequestBuilder,
+    validate: impl FnOnce(StatusCode, &str) -> bool,
+) -> HealthStatus {
+    let start = Instant::now();
+    let response = match request
+        .timeout(PROBE_TIMEOUT)
+        .send_step(provider_type, "health_check")
+        .await
+    {
+        Ok(response) => response,
+        Err(error) => {
+            return HealthStatus {
+                latency: start.elapsed(),
+                ..HealthStatus::unreachable(provider_type, error)
+            }
+        }
+    };
+    let status_code = response.status();
+    let challenge = is_cloudflare_challenge(&response);
+    let (state, message) = match response.text().await {
+        Ok(body) => classify(status_code, challenge, &body, validate),
+        Err(error) => (HealthState::Unhealthy, Some(error.to_string())),
+    };
+    HealthStatus {
+        provider_type,
+        state,
+        latency: start.elapsed(),
+        status_code: Some(status_code),
+        message,
+    }
+}
+
+/// Classifies a probe response, see [`probe`].
+///
+/// `challenge` is whether the response headers mark it as a Cloudflare challenge.
+fn classify(
+    status_code: StatusCode,
+    challenge: bool,
+    body: &str,
+    validate: impl FnOnce(StatusCode, &str) -> bool,
+) -> (HealthState, Option<String>) {
+    if status_code == StatusCode::TOO_MANY_REQUESTS {
+        (HealthState::RateLimited, None)
+    } else if challenge || body.contains("challenges.cloudflare.com") {
+        (
+            HealthState::Blocked,
+            Some("Cloudflare challenge".to_string()),
+        )
+    } else if status_code.is_server_error() {
+        (
+            HealthState::Unhealthy,
+            Some(format!("Invalid response status: {}", status_code)),
+        )
+    } else if !validate(status_code, body) {
+        (
+            HealthState::Unhealthy,
+            Some(format!("Unexpected response with status {}", status_code)),
+        )
+    } else {
+        (HealthState::Healthy, None)
+    }
+}
+
+fn is_cloudflare_challenge(response: &Response) -> bool {
+    response
+        .headers()
+        .get("cf-mitigated")
+        .is_some_and(|value| value == "challenge")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(status_code: StatusCode, challenge: bool, body: &str) -> HealthState {
+        classify(status_code, challenge, body, |status, body| {
+            status.is_success() && body == "ok"
+        })
+        .0
+    }
+
+    #[test]
+    fn test_classify() {
+        assert_eq!(HealthState::Healthy, state(StatusCode::OK, false, "ok"));
+        assert_eq!(
+            HealthState::RateLimited,
+            state(StatusCode::TOO_MANY_REQUESTS, true, "")
+        );
+        assert_eq!(HealthState::Blocked, state(StatusCode::FORBIDDEN, true, ""));
+        assert_eq!(
+            HealthState::Blocked,
+            state(
+                StatusCode::OK,
+                false,
+                "<script src=\"https://challenges.cloudflare.com/turnstile\">"
+            )
+        );
+        assert_eq!(
+            HealthState::Unhealthy,
+            state(StatusCode::BAD_GATEWAY, false, "ok")
+        );
+        assert_eq!(
+            HealthState::Unhealthy,
+            state(StatusCode::OK, false, "unexpected")
+        );
+        assert_eq!(
+            HealthState::Unhealthy,
+            state(StatusCode::NOT_FOUND, false, "ok")
+        );
+    }
+}
@@ -15,11 +15,15 @@
 //!
 //! You can use the following email providers for temporary email addresses:
 //!
+//! - [Mail.gw](https://mail.gw)
 //! - [Mail.tm](https://mail.tm)
 //! - [Muellmail.com](https://muellmail.com)
 //! - [TempMail.lol](https://tempmail.lol)
 //! - [FakeMail.net](https://fakemail.net)
 //!
+//! Other services serving the Mail.tm API, including self-hosted instances for testing, can be used with
+//! [`TempMail::api_url`].
+//!
 //! # Disclaimer
 //!
 //! This library is not affiliated with, endorsed by, or associated with the email providers listed above.
@@ -74,6 +78,129 @@
 //! [rquest]: https://github.com/0x676e67/rquest
 //! [reqwest]: https://github.com/seanmonstar/reqwest
 //!
+//! # Blocking API
+//!
+//! For synchronous code, the `blocking` feature flag enables the [`blocking`] module. It mirrors [`TempMail`]
+//! and [`Inbox`] with methods that block the current thread and manage an internal Tokio runtime.
+//!
+//! ```toml
+//! [dependencies]
+//! ephemeral_email = { version = "0.2", features = ["blocking"] }
+//! ```
+//!
+//! # Inbox Pool
+//!
+//! The `pool` feature flag enables the [`pool`] module. Its [`pool::InboxPool`] creates inboxes in the background
+//! and hands them out via [`pool::InboxPool::lease`], which avoids waiting for the inbox creation in tests.
+//!
+//! # Webhooks
+//!
+//! The `webhook` feature flag enables the [`forward`] module. Its [`forward::Forwarder`] watches inboxes and
+//! sends each new message as JSON to a URL, with retries, optional HMAC signing and a dead-letter log.
+//!
+//! # Serde
+//!
+//! The `serde` feature flag implements `Serialize` and `Deserialize` for [`Message`], [`MessageSummary`],
+//! [`EmailAddress`], [`Domain`], [`ProviderType`] and [`InboxSession`]. Addresses and domains are
+//! serialized as strings, and providers as their stable [`ProviderType::id`].
+//!
+//! # Tracing
+//!
+//! The `tracing` feature flag instruments inbox creation and message fetching with [tracing] spans and
+//! events. Every request to a provider is recorded with the provider, the step of the flow, the status
+//! code and the latency. Tokens and cookies are never recorded, and query parameters are redacted.
+//!
+//! [tracing]: https://github.com/tokio-rs/tracing
+//!
+//! # Metrics
+//!
+//! The `metrics` feature flag records inbox creations, fetch latencies, rate limits and received messages
+//! per provider with the [metrics facade], so they can be exported to Prometheus or other systems by
+//! installing a recorder. The metric names are listed in the [`metrics`] module.
+//!
+//! [metrics facade]: https://github.com/metrics-rs/metrics
+//!
+//! # Health Checks
+//!
+//! [`ProviderType::health_check`] sends a lightweight request to a provider, without creating an inbox, and
+//! returns its state and latency. [`ProviderType::health_check_all`] checks all providers concurrently.
+//!
+//! ```no_run
+//! use ephemeral_email::ProviderType;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     for status in ProviderType::health_check_all().await {
+//!         println!("{}: {} ({:?})", status.provider_type, status.state, status.latency);
+//!     }
+//! }
+//! ```
+//!
+//! # Filtering
+//!
+//! A [`MessageFilter`] selects messages by sender, subject, body or time. Pass it to
+//! [`Inbox::get_messages_matching`], [`Inbox::watch_matching`] or [`Inbox::wait_for_message`]. Where the
+//! provider supports it, the filter is applied server-side, otherwise after fetching the messages.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use ephemeral_email::{Inbox, MessageFilter};
+//!
+//! async fn example(inbox: Inbox) {
+//!     let filter = MessageFilter::new().from("noreply@example.com").subject("Verify");
+//!     if let Some(message) = inbox.wait_for_message(&filter, Duration::from_secs(60)).await.unwrap() {
+//!         println!("Body: {}", message.body);
+//!     }
+//! }
+//! ```
+//!
+//! # Expiry
+//!
+//! Temporary inboxes are deleted by their provider after a while. [`Inbox::expires_at`] returns when,
+//! where the provider reports it or documents the lifetime of inboxes. For providers supporting
+//! [`Capability::KeepAlive`], [`Inbox::keep_alive`] extends the lifetime, and [`Inbox::keep_alive_every`]
+//! returns a future that keeps the inbox alive in the background until it is dropped.
+//!
+//! ```no_run
+//! use std::time::Duration;
+//!
+//! use ephemeral_email::{Capability, TempMail};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let inbox = TempMail::new()
+//!         .require(Capability::KeepAlive)
+//!         .create_inbox()
+//!         .await
+//!         .unwrap();
+//!     tokio::spawn(inbox.keep_alive_every(Duration::from_secs(60)));
+//!     println!("{} expires at {:?}", inbox.get_email_address(), inbox.expires_at());
+//! }
+//! ```
+//!
+//! # Export
+//!
+//! [`Inbox::export`] writes all messages of an inbox to an mbox file or a Maildir directory, which
+//! standard mail clients can open. Where a provider does not offer the raw source of a message, its headers
+//! are synthesized from the [`Message`] fields.
+//!
+//! # Command-line tool
+//!
+//! The `cli` feature builds the `ephemeral-email` binary, which creates and watches inboxes from a shell.
+//! Created inboxes are stored in a state file, so later invocations can fetch their messages.
+//!
+//! ```sh
+//! cargo install ephemeral_email --features cli
+//! ephemeral-email create --provider mail.tm
+//! ephemeral-email wait --from noreply@example.com --timeout 60
+//! ephemeral-email export --output messages.json
+//! ephemeral-email export --format mbox --output messages.mbox
+//! ephemeral-email status
+//! ```
+//!
+//! Pass `--json` to any command for JSON output.
+//!
 //! # Related Crates
 //!
 //! Check out these related crates:
@@ -86,15 +213,43 @@
 //! If you are a representative of an email provider listed in this library and would like to request removal or
 //! have any concerns, please contact us at [ephemeral_email@hwiller.com](mailto:ephemeral_email@hwiller.com).
 
+#[cfg(feature = "blocking")]
+pub mod blocking;
+mod capabilities;
+mod catalog;
 mod client;
 mod domain;
 mod email;
 mod error;
+mod export;
+mod filter;
+#[cfg(feature = "webhook")]
+pub mod forward;
+mod health;
+pub mod metrics;
+pub mod name;
+#[cfg(feature = "pool")]
+pub mod pool;
 mod provider;
 mod temp_mail;
+mod trace;
 
+pub use capabilities::{Capabilities, Capability};
+pub use catalog::DomainCatalog;
 pub use domain::Domain;
-pub use email::{EmailAddress, Message};
-pub use error::{EmailAddressError, InboxCreationError, MessageFetcherError};
-pub use provider::{Inbox, ProviderType};
+pub use email::{EmailAddress, Mailbox, Message, MessageSummary};
+pub use error::{
+    DomainCatalogError, DomainError, EmailAddressError, ExportError, InboxCreationError,
+    MessageFetcherError, ProviderError, ProviderTypeError,
+};
+pub use export::ExportFormat;
+pub use filter::MessageFilter;
+pub use health::{HealthState, HealthStatus};
+pub use provider::{Inbox, InboxSession, ProviderType};
+/// Re-exported from the [`regex`](https://docs.rs/regex/1) crate, version 1.x, for
+/// [`MessageFilter::subject_regex`] and [`MessageFilter::body_regex`].
+///
+/// It is part of the public API, so upgrading `regex` to a new major version is a breaking change
+/// of this crate.
+pub use regex::Regex;
 pub use temp_mail::TempMail;
@@ -0,0 +1,186 @@
+//! Per-provider metrics recorded with the [`metrics`](https://docs.rs/metrics) facade.
+//!
+//! Metrics are only recorded with the `metrics` feature flag. They are sent to the recorder
+//! installed by the application, for example the Prometheus exporter of the
+//! `metrics-exporter-prometheus` crate. Every metric has a `provider` label with the
+//! [`ProviderType::id`] of the provider.
+
+use std::time::Instant;
+
+use crate::error::{InboxCreationError, MessageFetcherError};
+use crate::provider::{Inbox, ProviderType};
+
+/// Counts the attempts to create an inbox.
+pub const INBOX_CREATION_ATTEMPTS: &str = "ephemeral_email_inbox_creation_attempts_total";
+/// Counts the inboxes created successfully.
+pub const INBOX_CREATION_SUCCESSES: &str = "ephemeral_email_inbox_creation_successes_total";
+/// Counts the failed attempts to create an inbox.
+pub const INBOX_CREATION_FAILURES: &str = "ephemeral_email_inbox_creation_failures_total";
+/// Measures the duration of fetching messages in seconds, with an `operation` label.
+pub const FETCH_DURATION: &str = "ephemeral_email_fetch_duration_seconds";
+/// Counts the failed requests for messages, with an `operation` label.
+pub const FETCH_FAILURES: &str = "ephemeral_email_fetch_failures_total";
+/// Counts the requests the provider rejected because of too many requests.
+pub const RATE_LIMITED: &str = "ephemeral_email_rate_limited_total";
+/// Counts the messages returned by [`Inbox::get_new_messages`] and [`Inbox::watch`].
+pub const MESSAGES_RECEIVED: &str = "ephemeral_email_messages_received_total";
+
+/// Registers descriptions of all metrics with the installed recorder.
+///
+/// Calling this is optional, recorders use it to annotate exported metrics, like the `# HELP`
+/// lines of Prometheus.
+#[cfg(feature = "metrics")]
+pub fn describe_metrics() {
+    use metrics::{describe_counter, describe_histogram, Unit};
+
+    describe_counter!(INBOX_CREATION_ATTEMPTS, "Attempts to create an inbox");
+    describe_counter!(INBOX_CREATION_SUCCESSES, "Inboxes created successfully");
+    describe_counter!(
+        INBOX_CREATION_FAILURES,
+        "Failed attempts to create an inbox"
+    );
+    describe_histogram!(
+        FETCH_DURATION,
+        Unit::Seconds,
+        "Duration of fetching messages"
+    );
+    describe_counter!(FETCH_FAILURES, "Failed requests for messages");
+    describe_counter!(
+        RATE_LIMITED,
+        "Requests rejected because of too many requests"
+    );
+    describe_counter!(MESSAGES_RECEIVED, "New messages received");
+}
+
+/// Records an attempt to create an inbox.
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn record_creation_attempt(provider_type: ProviderType) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(INBOX_CREATION_ATTEMPTS, "provider" => provider_type.id()).increment(1);
+}
+
+/// Records the outcome of an attempt to create an inbox.
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn record_creation(
+    provider_type: ProviderType,
+    result: &Result<Inbox, InboxCreationError>,
+) {
+    #[cfg(feature = "metrics")]
+    match result {
+        Ok(_) => metrics::counter!(INBOX_CREATION_SUCCESSES, "provider" => provider_type.id())
+            .increment(1),
+        Err(error) => {
+            metrics::counter!(INBOX_CREATION_FAILURES, "provider" => provider_type.id())
+                .increment(1);
+            if error.is_rate_limited() {
+                metrics::counter!(RATE_LIMITED, "provider" => provider_type.id()).increment(1);
+            }
+        }
+    }
+}
+
+/// Records the duration and outcome of an `operation` fetching messages, which began at `start`.
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn record_fetch<T>(
+    provider_type: ProviderType,
+    operation: &'static str,
+    start: Instant,
+    result: &Result<T, MessageFetcherError>,
+) {
+    #[cfg(feature = "metrics")]
+    {
+        let labels = [("provider", provider_type.id()), ("operation", operation)];
+        metrics::histogram!(FETCH_DURATION, &labels).record(start.elapsed());
+        if let Err(error) = result {
+            metrics::counter!(FETCH_FAILURES, &labels).increment(1);
+            if error.is_rate_limited() {
+                metrics::counter!(RATE_LIMITED, "provider" => provider_type.id()).increment(1);
+            }
+        }
+    }
+}
+
+/// Records `count` new messages.
+#[cfg_attr(not(feature = "metrics"), allow(unused_variables))]
+pub(crate) fn record_messages_received(provider_type: ProviderType, count: usize) {
+    #[cfg(feature = "metrics")]
+    metrics::counter!(MESSAGES_RECEIVED, "provider" => provider_type.id()).increment(count as u64);
+}
+
+#[cfg(all(test, feature = "metrics"))]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use metrics::{
+        Counter, Gauge, Histogram, Key, KeyName, Metadata, Recorder, SharedString, Unit,
+    };
+
+    use super::*;
+
+    /// A recorder that keeps the names and labels of the registered metrics.
+    #[derive(Default)]
+    struct TestRecorder {
+        keys: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl TestRecorder {
+        fn register(&self, key: &Key) {
+            let labels: Vec<String> = key
+                .labels()
+                .map(|label| format!("{}={}", label.key(), label.value()))
+                .collect();
+            self.keys
+                .lock()
+                .unwrap()
+                .push(format!("{}{{{}}}", key.name(), labels.join(",")));
+        }
+    }
+
+    impl Recorder for TestRecorder {
+        fn describe_counter(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_gauge(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+        fn describe_histogram(&self, _: KeyName, _: Option<Unit>, _: SharedString) {}
+
+        fn register_counter(&self, key: &Key, _: &Metadata<'_>) -> Counter {
+            self.register(key);
+            Counter::noop()
+        }
+
+        fn register_gauge(&self, key: &Key, _: &Metadata<'_>) -> Gauge {
+            self.register(key);
+            Gauge::noop()
+        }
+
+        fn register_histogram(&self, key: &Key, _: &Metadata<'_>) -> Histogram {
+            self.register(key);
+            Histogram::noop()
+        }
+    }
+
+    #[test]
+    fn test_recorded_metrics() {
+        let recorder = TestRecorder::default();
+        let keys = recorder.keys.clone();
+        metrics::with_local_recorder(&recorder, || {
+            record_creation_attempt(ProviderType::MailTm);
+            let result: Result<(), MessageFetcherError> =
+                Err(MessageFetcherError::MessageNotFound("1".to_string()));
+            record_fetch(
+                ProviderType::TempMailLol,
+                "get_messages",
+                Instant::now(),
+                &result,
+            );
+            record_messages_received(ProviderType::FakeMailNet, 2);
+        });
+        assert_eq!(
+            *keys.lock().unwrap(),
+            [
+                "ephemeral_email_inbox_creation_attempts_total{provider=mail_tm}",
+                "ephemeral_email_fetch_duration_seconds{provider=tempmail_lol,operation=get_messages}",
+                "ephemeral_email_fetch_failures_total{provider=tempmail_lol,operation=get_messages}",
+                "ephemeral_email_messages_received_total{provider=fakemail_net}",
+            ]
+        );
+    }
+}
@@ -0,0 +1,344 @@
+//! Generators for the name before the `@` of new inboxes.
+//!
+//! By default [`crate::TempMail`] picks 8 random lowercase letters and digits, which some sites
+//! reject as suspicious. A [`NameGenerator`] set with [`crate::TempMail::name_generator`] can
+//! produce more natural names instead. Generated names are adjusted to the [`NameRules`] of the
+//! provider before use, explicitly set names are used as they are.
+//!
+//! # Example
+//! ```no_run
+//! use ephemeral_email::name::{NameTemplate, RealisticName};
+//! use ephemeral_email::TempMail;
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     // Creates addresses like `qa-jane.miller@...`.
+//!     let inbox = TempMail::new()
+//!         .name_generator(NameTemplate::new("qa-{name}", RealisticName::new()))
+//!         .create_inbox()
+//!         .await
+//!         .unwrap();
+//! }
+//! ```
+
+use rand::seq::IndexedRandom;
+use rand::{Rng, RngCore};
+
+const FIRST_NAMES: &[&str] = &[
+    "alex", "anna", "ben", "carla", "chris", "daniel", "david", "emily", "emma", "eric", "felix",
+    "hannah", "jack", "jake", "jana", "jane", "john", "julia", "kate", "laura", "lena", "leo",
+    "lisa", "lucas", "maria", "mark", "max", "mia", "nina", "noah", "olivia", "paul", "peter",
+    "sara", "simon", "sophie", "thomas", "tim", "tom", "zoe",
+];
+
+const LAST_NAMES: &[&str] = &[
+    "adams", "baker", "becker", "brown", "clark", "davis", "evans", "fischer", "garcia", "green",
+    "hall", "harris", "hill", "hoffmann", "jones", "king", "koch", "lee", "lewis", "martin",
+    "meyer", "miller", "moore", "muller", "nelson", "parker", "richter", "schmidt", "schulz",
+    "scott", "smith", "taylor", "thomas", "turner", "walker", "weber", "white", "wilson", "wagner",
+    "young",
+];
+
+const CONSONANTS: &[u8] = b"bcdfghjklmnprstvz";
+const VOWELS: &[u8] = b"aeiou";
+
+/// Generates names for new inboxes.
+///
+/// The generator receives the random number generator of the [`crate::TempMail`] builder. It is
+/// implemented for closures, so a custom scheme does not need its own type:
+///
+/// ```
+/// use ephemeral_email::name::NameGenerator;
+/// use rand::{Rng, RngCore};
+///
+/// fn numbered(rng: &mut dyn RngCore) -> String {
+///     format!("user{}", rng.random_range(1000..10000))
+/// }
+///
+/// assert!(numbered.generate(&mut rand::rng()).starts_with("user"));
+/// ```
+pub trait NameGenerator: Send + Sync {
+    /// Returns a new name.
+    fn generate(&self, rng: &mut dyn RngCore) -> String;
+}
+
+impl<F> NameGenerator for F
+where
+    F: Fn(&mut dyn RngCore) -> String + Send + Sync,
+{
+    fn generate(&self, rng: &mut dyn RngCore) -> String {
+        self(rng)
+    }
+}
+
+/// Generates names of random characters.
+///
+/// Defaults to 8 lowercase letters and digits.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RandomName {
+    length: usize,
+    charset: Vec<char>,
+}
+
+impl RandomName {
+    /// Creates a generator for names of 8 lowercase letters and digits.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the length of the names.
+    pub fn length(mut self, length: usize) -> Self {
+        self.length = length;
+        self
+    }
+
+    /// Sets the characters the names are made of. Empty charsets are ignored.
+    pub fn charset(mut self, charset: &str) -> Self {
+        if !charset.is_empty() {
+            self.charset = charset.chars().collect();
+        }
+        self
+    }
+}
+
+impl Default for RandomName {
+    fn default() -> Self {
+        Self {
+            length: 8,
+            charset: "abcdefghijklmnopqrstuvwxyz0123456789".chars().collect(),
+        }
+    }
+}
+
+impl NameGenerator for RandomName {
+    fn generate(&self, rng: &mut dyn RngCore) -> String {
+        (0..self.length)
+            .map(|_| self.charset[rng.random_range(0..self.charset.len())])
+            .collect()
+    }
+}
+
+/// Generates names that look like a person's, like `jane.miller` or `jane.miller42`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RealisticName {
+    separator: String,
+    digits: usize,
+}
+
+impl RealisticName {
+    /// Creates a generator for names like `jane.miller`.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the separator between the first and last name. Defaults to `.`.
+    pub fn separator(mut self, separator: impl Into<String>) -> Self {
+        self.separator = separator.into();
+        self
+    }
+
+    /// Sets how many random digits are appended. Defaults to 0.
+    pub fn digits(mut self, digits: usize) -> Self {
+        self.digits = digits;
+        self
+    }
+}
+
+impl Default for RealisticName {
+    fn default() -> Self {
+        Self {
+            separator: ".".to_string(),
+            digits: 0,
+        }
+    }
+}
+
+impl NameGenerator for RealisticName {
+    fn generate(&self, rng: &mut dyn RngCore) -> String {
+        let first = FIRST_NAMES.choose(rng).copied().unwrap_or_default();
+        let last = LAST_NAMES.choose(rng).copied().unwrap_or_default();
+        let digits: String = (0..self.digits)
+            .map(|_| char::from(b'0' + rng.random_range(0..10)))
+            .collect();
+        format!("{}{}{}{}", first, self.separator, last, digits)
+    }
+}
+
+/// Generates pronounceable made-up words of alternating consonants and vowels, like `tokaru`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PronounceableName {
+    syllables: usize,
+}
+
+impl PronounceableName {
+    /// Creates a generator for words of 3 syllables.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the number of syllables.
+    pub fn syllables(mut self, syllables: usize) -> Self {
+        self.syllables = syllables;
+        self
+    }
+}
+
+impl Default for PronounceableName {
+    fn default() -> Self {
+        Self { syllables: 3 }
+    }
+}
+
+impl NameGenerator for PronounceableName {
+    fn generate(&self, rng: &mut dyn RngCore) -> String {
+        let mut name = String::with_capacity(self.syllables * 2);
+        for _ in 0..self.syllables {
+            name.push(char::from(*CONSONANTS.choose(rng).unwrap_or(&b'b')));
+            name.push(char::from(*VOWELS.choose(rng).unwrap_or(&b'a')));
+        }
+        name
+    }
+}
+
+/// Wraps the names of another generator in a template, for adding a prefix or suffix.
+///
+/// `{name}` in the template is replaced by the generated name.
+///
+/// # Example
+/// ```
+/// use ephemeral_email::name::{NameGenerator, NameTemplate, RandomName};
+///
+/// let generator = NameTemplate::new("signup-{name}-test", RandomName::new().length(4));
+/// let name = generator.generate(&mut rand::rng());
+/// assert!(name.starts_with("signup-") && name.ends_with("-test"));
+/// ```
+pub struct NameTemplate {
+    template: String,
+    generator: Box<dyn NameGenerator>,
+}
+
+impl NameTemplate {
+    /// Creates a template filled with names of `generator`.
+    pub fn new(template: impl Into<String>, generator: impl NameGenerator + 'static) -> Self {
+        Self {
+            template: template.into(),
+            generator: Box::new(generator),
+        }
+    }
+}
+
+impl NameGenerator for NameTemplate {
+    fn generate(&self, rng: &mut dyn RngCore) -> String {
+        self.template
+            .replace("{name}", &self.generator.generate(rng))
+    }
+}
+
+/// The names a provider accepts.
+///
+/// Generated names are adjusted with [`NameRules::apply`] before they are used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct NameRules {
+    /// The minimum length of a name.
+    pub min_length: usize,
+    /// The maximum length of a name.
+    pub max_length: usize,
+    /// The characters allowed besides lowercase letters and digits. They are not allowed at the
+    /// start or end of a name.
+    pub symbols: &'static str,
+    /// Whether uppercase letters are allowed.
+    pub uppercase: bool,
+}
+
+impl Default for NameRules {
+    fn default() -> Self {
+        Self {
+            min_length: 3,
+            max_length: 30,
+            symbols: ".-_",
+            uppercase: false,
+        }
+    }
+}
+
+impl NameRules {
+    /// Adjusts `name` to the rules.
+    ///
+    /// Disallowed characters are removed, uppercase letters are converted if needed, runs of
+    /// symbols are collapsed to their first symbol and symbols at the start or end are removed.
+    /// Long names are shortened and short names are padded with random digits.
+    pub fn apply(&self, name: &str, rng: &mut dyn RngCore) -> String {
+        let name = if self.uppercase {
+            name.to_string()
+        } else {
+            name.to_ascii_lowercase()
+        };
+        let is_symbol = |c: char| self.symbols.contains(c);
+        let mut name = name.chars().fold(String::new(), |mut name, c| {
+            let follows_symbol = name.is_empty() || name.ends_with(is_symbol);
+            if c.is_ascii_alphanumeric() || is_symbol(c) && !follows_symbol {
+                name.push(c);
+            }
+            name
+        });
+        name.truncate(self.max_length);
+        name.truncate(name.trim_end_matches(is_symbol).len());
+        while name.len() < self.min_length {
+            name.push(char::from(b'0' + rng.random_range(0..10)));
+        }
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    use super::*;
+
+    #[test]
+    fn test_random_name() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let name = RandomName::new().length(5).charset("ab").generate(&mut rng);
+        assert_eq!(name.len(), 5);
+        assert!(name.chars().all(|c| c == 'a' || c == 'b'));
+    }
+
+    #[test]
+    fn test_realistic_name() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let name = RealisticName::new().digits(2).generate(&mut rng);
+        let (first, last) = name.split_once('.').unwrap();
+        assert!(FIRST_NAMES.contains(&first));
+        assert!(last[..last.len() - 2]
+            .chars()
+            .all(|c| c.is_ascii_lowercase()));
+        assert!(last[last.len() - 2..].chars().all(|c| c.is_ascii_digit()));
+    }
+
+    #[test]
+    fn test_pronounceable_name() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let name = PronounceableName::new().syllables(4).generate(&mut rng);
+        assert_eq!(name.len(), 8);
+        for (i, byte) in name.bytes().enumerate() {
+            assert_eq!(VOWELS.contains(&byte), i % 2 == 1);
+        }
+    }
+
+    #[test]
+    fn test_name_rules_apply() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let rules = NameRules::default();
+        assert_eq!(rules.apply("-Jane Miller!-", &mut rng), "janemiller");
+        assert_eq!(rules.apply("a..b-", &mut rng), "a.b");
+        assert_eq!(rules.apply("._a-_.b", &mut rng), "a-b");
+        assert_eq!(rules.apply(&"a".repeat(40), &mut rng).len(), 30);
+
+        let padded = rules.apply("a", &mut rng);
+        assert_eq!(padded.len(), 3);
+        assert!(padded[1..].chars().all(|c| c.is_ascii_digit()));
+    }
+}
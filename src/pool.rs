@@ -0,0 +1,356 @@
+//! A pool of pre-created inboxes.
+//!
+//! Creating an inbox takes several requests for most providers. An [`InboxPool`] creates inboxes in
+//! the background, so [`InboxPool::lease`] usually returns immediately. Leased inboxes are replaced
+//! automatically, and returned inboxes are discarded or deleted depending on the [`ReturnPolicy`].
+//!
+//! This module is gated behind the `pool` feature flag and requires a Tokio runtime.
+//!
+//! # Example
+//! ```no_run
+//! use ephemeral_email::pool::InboxPool;
+//! use ephemeral_email::{ProviderType, TempMail};
+//!
+//! #[tokio::main]
+//! async fn main() {
+//!     let pool = InboxPool::builder(TempMail::new().provider_type(ProviderType::MailTm))
+//!         .size(3)
+//!         .build();
+//!
+//!     let inbox = pool.lease().await.unwrap();
+//!     println!("Leased inbox with email: {}", inbox.get_email_address());
+//! }
+//! ```
+
+use std::future::Future;
+use std::ops::{Deref, DerefMut};
+use std::time::Duration;
+
+use tokio::sync::{mpsc, Mutex};
+
+use crate::{Inbox, InboxCreationError, TempMail};
+
+/// What happens to an inbox when its [`InboxLease`] is dropped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[non_exhaustive]
+pub enum ReturnPolicy {
+    /// The inbox is dropped and left to expire at the provider.
+    #[default]
+    Discard,
+    /// The inbox is deleted at the provider. Errors, for example from providers that do not support
+    /// deletion, are ignored.
+    Delete,
+}
+
+/// A builder for an [`InboxPool`].
+#[derive(Debug, Clone)]
+pub struct InboxPoolBuilder {
+    template: TempMail,
+    size: usize,
+    concurrency: usize,
+    return_policy: ReturnPolicy,
+    retry_delay: Duration,
+}
+
+impl InboxPoolBuilder {
+    /// Sets the number of inboxes kept ready in the pool. Defaults to 5.
+    pub fn size(mut self, size: usize) -> Self {
+        self.size = size.max(1);
+        self
+    }
+
+    /// Sets how many inboxes are created at the same time. Defaults to 1.
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = concurrency.max(1);
+        self
+    }
+
+    /// Sets what happens to inboxes when their lease is dropped. Defaults to [`ReturnPolicy::Discard`].
+    pub fn return_policy(mut self, return_policy: ReturnPolicy) -> Self {
+        self.return_policy = return_policy;
+        self
+    }
+
+    /// Sets how long to wait after a failed inbox creation before trying again. Defaults to 5 seconds.
+    pub fn retry_delay(mut self, retry_delay: Duration) -> Self {
+        self.retry_delay = retry_delay;
+        self
+    }
+
+    /// Creates the pool and starts creating inboxes in the background.
+    ///
+    /// # Panics
+    ///
+    /// Panics if called outside of a Tokio runtime.
+    pub fn build(self) -> InboxPool {
+        let template = self.template.clone();
+        self.build_with(move || template.clone().create_inbox())
+    }
+
+    /// Creates the pool with inboxes created by `create` instead of the template.
+    fn build_with<F, Fut>(self, create: F) -> InboxPool
+    where
+        F: FnMut() -> Fut + Clone + Send + 'static,
+        Fut: Future<Output = Result<Inbox, InboxCreationError>> + Send + 'static,
+    {
+        let (inboxes, receiver) = mpsc::channel(self.size);
+        for _ in 0..self.concurrency {
+            tokio::spawn(replenish(create.clone(), inboxes.clone(), self.retry_delay));
+        }
+
+        let (returns, returned) = mpsc::unbounded_channel();
+        tokio::spawn(handle_returns(returned, self.return_policy));
+
+        InboxPool {
+            receiver: Mutex::new(receiver),
+            returns,
+        }
+    }
+}
+
+/// A pool of inboxes that are created in the background and handed out via [`InboxPool::lease`].
+///
+/// All inboxes are created from a [`TempMail`] template. If the template sets a name, make sure the
+/// provider allows creating multiple inboxes with that name, or leave the name unset.
+///
+/// Dropping the pool stops the creation of new inboxes.
+pub struct InboxPool {
+    receiver: Mutex<mpsc::Receiver<Result<Inbox, InboxCreationError>>>,
+    returns: mpsc::UnboundedSender<Inbox>,
+}
+
+impl InboxPool {
+    /// Creates a builder for a pool creating inboxes from `template`.
+    pub fn builder(template: TempMail) -> InboxPoolBuilder {
+        InboxPoolBuilder {
+            template,
+            size: 5,
+            concurrency: 1,
+            return_policy: ReturnPolicy::default(),
+            retry_delay: Duration::from_secs(5),
+        }
+    }
+
+    /// Leases an inbox from the pool, waiting until one is available.
+    ///
+    /// The inbox is returned to the pool when the [`InboxLease`] is dropped. If the background
+    /// creation of an inbox failed, that error is returned instead.
+    pub async fn lease(&self) -> Result<InboxLease, InboxCreationError> {
+        let inbox = self
+            .receiver
+            .lock()
+            .await
+            .recv()
+            .await
+            .ok_or(InboxCreationError::PoolShutDown)??;
+        Ok(InboxLease {
+            inbox: Some(inbox),
+            returns: self.returns.clone(),
+        })
+    }
+}
+
+/// An inbox leased from an [`InboxPool`].
+///
+/// Dereferences to the leased [`Inbox`]. When dropped, the inbox is handed back to the pool, which
+/// applies its [`ReturnPolicy`].
+pub struct InboxLease {
+    inbox: Option<Inbox>,
+    returns: mpsc::UnboundedSender<Inbox>,
+}
+
+impl InboxLease {
+    /// Takes the inbox out of the lease, so it is not returned to the pool.
+    pub fn into_inner(mut self) -> Inbox {
+        self.inbox
+            .take()
+            .expect("lease holds an inbox until dropped")
+    }
+}
+
+impl Deref for InboxLease {
+    type Target = Inbox;
+
+    fn deref(&self) -> &Inbox {
+        self.inbox
+            .as_ref()
+            .expect("lease holds an inbox until dropped")
+    }
+}
+
+impl DerefMut for InboxLease {
+    fn deref_mut(&mut self) -> &mut Inbox {
+        self.inbox
+            .as_mut()
+            .expect("lease holds an inbox until dropped")
+    }
+}
+
+impl Drop for InboxLease {
+    fn drop(&mut self) {
+        if let Some(inbox) = self.inbox.take() {
+            let _ = self.returns.send(inbox);
+        }
+    }
+}
+
+async fn replenish<F, Fut>(
+    mut create: F,
+    inboxes: mpsc::Sender<Result<Inbox, InboxCreationError>>,
+    retry_delay: Duration,
+) where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<Inbox, InboxCreationError>>,
+{
+    // Reserving a slot first keeps the number of created inboxes within the pool size.
+    while let Ok(permit) = inboxes.reserve().await {
+        let inbox = create().await;
+        let failed = inbox.is_err();
+        permit.send(inbox);
+        if failed {
+            tokio::time::sleep(retry_delay).await;
+        }
+    }
+}
+
+async fn handle_returns(mut returned: mpsc::UnboundedReceiver<Inbox>, return_policy: ReturnPolicy) {
+    while let Some(inbox) = returned.recv().await {
+        if return_policy == ReturnPolicy::Delete {
+            let _ = inbox.delete().await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::provider::MessageFetcher;
+    use crate::{Message, MessageFetcherError, ProviderType};
+
+    /// A fetcher counting how often its inbox was deleted.
+    struct CountingFetcher(Arc<AtomicUsize>);
+
+    #[async_trait::async_trait]
+    impl MessageFetcher for CountingFetcher {
+        async fn fetch_messages(&mut self) -> Result<Vec<Message>, MessageFetcherError> {
+            Ok(Vec::new())
+        }
+
+        fn get_credentials(&self) -> String {
+            String::new()
+        }
+
+        async fn delete_inbox(&mut self) -> Result<(), MessageFetcherError> {
+            self.0.fetch_add(1, Ordering::SeqCst);
+            Ok(())
+        }
+    }
+
+    /// Creates a pool whose inboxes are created without network access.
+    ///
+    /// Returns counters of the created and deleted inboxes. The creations listed in `failures`,
+    /// counted from zero, fail.
+    fn fake_pool(
+        builder: InboxPoolBuilder,
+        failures: &'static [usize],
+    ) -> (InboxPool, Arc<AtomicUsize>, Arc<AtomicUsize>) {
+        let created = Arc::new(AtomicUsize::new(0));
+        let deleted = Arc::new(AtomicUsize::new(0));
+        let pool = builder.retry_delay(Duration::from_millis(1)).build_with({
+            let created = created.clone();
+            let deleted = deleted.clone();
+            move || {
+                let count = created.fetch_add(1, Ordering::SeqCst);
+                let deleted = deleted.clone();
+                async move {
+                    if failures.contains(&count) {
+                        return Err(InboxCreationError::DomainNotSupported);
+                    }
+                    Ok(Inbox::new(
+                        format!("test{}@mail.tm", count).parse().unwrap(),
+                        ProviderType::MailTm,
+                        CountingFetcher(deleted),
+                    ))
+                }
+            }
+        });
+        (pool, created, deleted)
+    }
+
+    /// Waits until `counter` reaches `value`, failing the test after a second.
+    async fn wait_for(counter: &AtomicUsize, value: usize) {
+        tokio::time::timeout(Duration::from_secs(1), async {
+            while counter.load(Ordering::SeqCst) < value {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        })
+        .await
+        .expect("counter did not reach the value in time");
+    }
+
+    fn builder() -> InboxPoolBuilder {
+        InboxPool::builder(TempMail::new())
+    }
+
+    #[tokio::test]
+    async fn test_pool_size_is_bounded() {
+        let (pool, created, _) = fake_pool(builder().size(2).concurrency(3), &[]);
+        wait_for(&created, 2).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        // Each task may have reserved a slot, but only two inboxes are created up front.
+        assert_eq!(2, created.load(Ordering::SeqCst));
+
+        let lease = pool.lease().await.unwrap();
+        assert_eq!("test0@mail.tm", lease.get_email_address().to_string());
+        wait_for(&created, 3).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(3, created.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_creation_error_is_leased() {
+        let (pool, _, _) = fake_pool(builder().size(1), &[0]);
+        assert!(matches!(
+            pool.lease().await,
+            Err(InboxCreationError::DomainNotSupported)
+        ));
+        let lease = pool.lease().await.unwrap();
+        assert_eq!("test1@mail.tm", lease.get_email_address().to_string());
+    }
+
+    #[tokio::test]
+    async fn test_return_policy() {
+        let (pool, _, deleted) = fake_pool(builder().size(1), &[]);
+        drop(pool.lease().await.unwrap());
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(0, deleted.load(Ordering::SeqCst));
+
+        let (pool, _, deleted) =
+            fake_pool(builder().size(1).return_policy(ReturnPolicy::Delete), &[]);
+        drop(pool.lease().await.unwrap().into_inner());
+        drop(pool.lease().await.unwrap());
+        wait_for(&deleted, 1).await;
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(1, deleted.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_drop_stops_creation() {
+        let (pool, created, _) = fake_pool(builder().size(1).concurrency(2), &[]);
+        wait_for(&created, 1).await;
+        drop(pool);
+        tokio::time::timeout(Duration::from_secs(1), async {
+            // The creation closures, holding a clone of the counter each, are dropped with their
+            // tasks.
+            while Arc::strong_count(&created) > 1 {
+                tokio::time::sleep(Duration::from_millis(1)).await;
+            }
+        })
+        .await
+        .expect("creation tasks did not stop");
+        assert_eq!(1, created.load(Ordering::SeqCst));
+    }
+}
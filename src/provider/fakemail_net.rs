@@ -1,20 +1,53 @@
-use std::{sync::Arc, vec};
+use std::sync::Arc;
 
-use crate::client::Client;
-use crate::{Domain, InboxCreationError, Message, MessageFetcherError};
-use futures::lock::Mutex;
+use crate::capabilities::Capabilities;
+use crate::client::{Client, Jar};
+use crate::health::{probe, HealthStatus};
+use crate::name::NameRules;
+use crate::trace::SendStep;
+use crate::{
+    EmailAddress, InboxCreationError, Mailbox, Message, MessageFetcherError, MessageFilter,
+    MessageSummary,
+};
+use chrono::{DateTime, Utc};
 
-use super::{Inbox, Provider, ProviderType};
+use super::{
+    content_hash, export_cookies, import_cookies, Inbox, MessageCache, Provider, ProviderOptions,
+    ProviderType,
+};
 
-pub(crate) struct FakeMailNetProvider {}
+const BASE_URL: &str = "https://www.fakemail.net/";
+
+pub(crate) struct FakeMailNetProvider {
+    options: ProviderOptions,
+}
 
 pub(crate) struct FakeMailNetMessageFetcher {
     client: Client,
+    address: EmailAddress,
+    cookies: Arc<Jar>,
+    fetch_concurrency: usize,
+    cache: MessageCache,
 }
 
 impl FakeMailNetProvider {
-    pub fn new() -> Self {
-        Self {}
+    pub(crate) const CAPABILITIES: Capabilities = Capabilities {
+        custom_name: true,
+        resumption: true,
+        keep_alive: true,
+        ..Capabilities::NONE
+    };
+
+    /// The email check of the site only accepts letters, digits, dots and underscores.
+    pub(crate) const NAME_RULES: NameRules = NameRules {
+        min_length: 3,
+        max_length: 30,
+        symbols: "._",
+        uppercase: false,
+    };
+
+    pub fn new(options: ProviderOptions) -> Self {
+        Self { options }
     }
 }
 
@@ -33,19 +66,23 @@ struct EmailListEntry {
 
 async fn get_csrf_token(client: &Client) -> Result<String, InboxCreationError> {
     let response = client
-        .get("https://www.fakemail.net/")
-        .send()
-        .await?
-        .text()
+        .get(BASE_URL)
+        .send_step(ProviderType::FakeMailNet, "csrf")
         .await?;
+    if !response.status().is_success() {
+        return Err(InboxCreationError::from_response(ProviderType::FakeMailNet, response).await);
+    }
+    let response = response.text().await?;
     let csrf_token = response
         .split_once("CSRF=\"")
-        .ok_or(InboxCreationError::CreationError(
+        .ok_or(InboxCreationError::UnexpectedResponse(
+            ProviderType::FakeMailNet,
             "Failed to find CSRF token".to_string(),
         ))?
         .1
         .split_once("\"")
-        .ok_or(InboxCreationError::CreationError(
+        .ok_or(InboxCreationError::UnexpectedResponse(
+            ProviderType::FakeMailNet,
             "Failed to extract CSRF token".to_string(),
         ))?
         .0;
@@ -58,27 +95,34 @@ impl Provider for FakeMailNetProvider {
         &mut self,
         name: &str,
     ) -> Result<Inbox, InboxCreationError> {
-        let client = Client::builder().cookie_store(true).build()?;
+        let cookies = Arc::new(Jar::default());
+        let client = Client::builder().cookie_provider(cookies.clone()).build()?;
         let csrf_token = get_csrf_token(&client).await?;
         let response = client
             .get("https://www.fakemail.net/index/index")
             .header("X-Requested-With", "XMLHttpRequest")
             .query(&[("csrf_token", csrf_token)])
-            .send()
+            .send_step(ProviderType::FakeMailNet, "index")
             .await?;
         if !response.status().is_success() {
-            return Err(InboxCreationError::CreationError(
-                response.text().await.unwrap_or_default(),
-            ));
+            return Err(
+                InboxCreationError::from_response(ProviderType::FakeMailNet, response).await,
+            );
         }
         let check_response = client
             .post("https://www.fakemail.net/index/email-check")
             .header("X-Requested-With", "XMLHttpRequest")
             .form(&[("email", name), ("format", "json")])
-            .send()
-            .await?
-            .text()
+            .send_step(ProviderType::FakeMailNet, "email_check")
             .await?;
+        if !check_response.status().is_success() {
+            return Err(InboxCreationError::from_response(
+                ProviderType::FakeMailNet,
+                check_response,
+            )
+            .await);
+        }
+        let check_response = check_response.text().await?;
         if check_response != "\"ok\"" {
             return Err(InboxCreationError::NameTaken(name.into()));
         }
@@ -87,71 +131,271 @@ impl Provider for FakeMailNetProvider {
             .post("https://www.fakemail.net/index/new-email")
             .header("X-Requested-With", "XMLHttpRequest")
             .form(&[("emailInput", name), ("format", "json")])
-            .send()
-            .await?
-            .text()
+            .send_step(ProviderType::FakeMailNet, "new_email")
             .await?;
+        if !create_response.status().is_success() {
+            return Err(InboxCreationError::from_response(
+                ProviderType::FakeMailNet,
+                create_response,
+            )
+            .await);
+        }
+        let create_response = create_response.text().await?;
         if create_response != "\"ok\"" {
-            return Err(InboxCreationError::CreationError(create_response));
+            return Err(InboxCreationError::UnexpectedResponse(
+                ProviderType::FakeMailNet,
+                create_response,
+            ));
         }
         let csrf_token = get_csrf_token(&client).await?;
         let response = client
             .get("https://www.fakemail.net/index/index")
             .header("X-Requested-With", "XMLHttpRequest")
             .query(&[("csrf_token", csrf_token)])
-            .send()
-            .await?
-            .text()
+            .send_step(ProviderType::FakeMailNet, "index")
             .await?;
+        if !response.status().is_success() {
+            return Err(
+                InboxCreationError::from_response(ProviderType::FakeMailNet, response).await,
+            );
+        }
+        let response = response.text().await?;
         let response = response.trim();
         let index_response: IndexResponse = serde_json::from_str(response.trim()).map_err(|e| {
-            InboxCreationError::CreationError(format!("Failed to parse JSON: {}", e))
+            InboxCreationError::UnexpectedResponse(
+                ProviderType::FakeMailNet,
+                format!("Failed to parse JSON: {}", e),
+            )
         })?;
-        Ok(Inbox {
-            message_fetcher: Arc::new(Mutex::new(FakeMailNetMessageFetcher { client })),
-            email_address: index_response.email.parse()?,
-        })
+        let address: EmailAddress = index_response.email.parse()?;
+        Ok(Inbox::new(
+            address.clone(),
+            ProviderType::FakeMailNet,
+            FakeMailNetMessageFetcher::new(client, address, cookies, &self.options),
+        ))
+    }
+
+    async fn resume_inbox(
+        &mut self,
+        email_address: EmailAddress,
+        credentials: &str,
+    ) -> Result<Inbox, InboxCreationError> {
+        let cookies = import_cookies(credentials, BASE_URL);
+        let client = Client::builder().cookie_provider(cookies.clone()).build()?;
+        Ok(Inbox::new(
+            email_address.clone(),
+            ProviderType::FakeMailNet,
+            FakeMailNetMessageFetcher::new(client, email_address, cookies, &self.options),
+        ))
     }
 
     fn get_provider_type(&self) -> ProviderType {
         ProviderType::FakeMailNet
     }
 
-    fn get_domains(&self) -> Vec<Domain> {
-        vec![]
+    async fn health_check(&self) -> HealthStatus {
+        probe(
+            ProviderType::FakeMailNet,
+            Client::new().get(BASE_URL),
+            |status, body| status.is_success() && body.contains("CSRF=\""),
+        )
+        .await
+    }
+}
+
+impl FakeMailNetMessageFetcher {
+    fn new(
+        client: Client,
+        address: EmailAddress,
+        cookies: Arc<Jar>,
+        options: &ProviderOptions,
+    ) -> Self {
+        Self {
+            client,
+            address,
+            cookies,
+            fetch_concurrency: options.fetch_concurrency,
+            cache: MessageCache::default(),
+        }
+    }
+
+    /// Returns the listed messages that may match `filter`, fetching those not cached
+    /// concurrently.
+    ///
+    /// `summaries` must be the complete list, as messages no longer listed are removed from the
+    /// cache.
+    async fn fetch_listed(
+        &self,
+        summaries: Vec<MessageSummary>,
+        filter: &MessageFilter,
+    ) -> Result<Vec<Message>, MessageFetcherError> {
+        self.cache.retain(
+            &summaries
+                .iter()
+                .map(|summary| summary.id.as_str())
+                .collect::<Vec<_>>(),
+        );
+        self.cache
+            .fetch_matching(summaries, filter, self.fetch_concurrency, |summary| {
+                self.fetch_email(summary)
+            })
+            .await
+    }
+
+    async fn fetch_email(&self, summary: MessageSummary) -> Result<Message, MessageFetcherError> {
+        let response = self
+            .client
+            .get(format!(
+                "https://www.fakemail.net/email/id/{}",
+                position(&summary.id)
+            ))
+            .header("X-Requested-With", "XMLHttpRequest")
+            .send_step(ProviderType::FakeMailNet, "message")
+            .await?;
+        if !response.status().is_success() {
+            return Err(
+                MessageFetcherError::from_response(ProviderType::FakeMailNet, response).await,
+            );
+        }
+        let body = response.text().await?;
+        Ok(Message {
+            id: summary.id,
+            from: summary.from,
+            to: vec![self.address.clone().into()],
+            cc: Vec::new(),
+            reply_to: Vec::new(),
+            subject: summary.subject,
+            body,
+            date: summary.date,
+        })
     }
 }
 
+/// Returns the summaries of the listed messages.
+///
+/// The list is ordered newest first, and the messages are numbered from the oldest one, which is a
+/// welcome message. The numbers shift when a message is deleted, so the ids combine the number with
+/// a hash of the sender and subject, to not mistake another message for one seen before.
+fn summaries(email_list: Vec<EmailListEntry>) -> Vec<MessageSummary> {
+    email_list
+        .into_iter()
+        .rev()
+        .enumerate()
+        .skip(1)
+        .map(|(i, email)| MessageSummary {
+            id: format!(
+                "{}-{:016x}",
+                i + 1,
+                content_hash(&[&email.from, &email.subject])
+            ),
+            from: Mailbox::from_header(&email.from),
+            subject: email.subject,
+            date: None,
+            size: None,
+        })
+        .collect()
+}
+
+/// Returns the number of the message with `id` in the list.
+fn position(id: &str) -> &str {
+    id.split_once('-').map_or(id, |(position, _)| position)
+}
+
 #[async_trait::async_trait]
 impl super::MessageFetcher for FakeMailNetMessageFetcher {
     async fn fetch_messages(&mut self) -> Result<Vec<Message>, MessageFetcherError> {
-        let email_list_response = self
+        let summaries = self.list_summaries().await?;
+        self.fetch_listed(summaries, &MessageFilter::new()).await
+    }
+
+    async fn list_summaries(&mut self) -> Result<Vec<MessageSummary>, MessageFetcherError> {
+        let response = self
             .client
             .get("https://www.fakemail.net/index/refresh")
             .header("X-Requested-With", "XMLHttpRequest")
-            .send()
-            .await?
-            .text()
+            .send_step(ProviderType::FakeMailNet, "refresh")
             .await?;
+        if !response.status().is_success() {
+            return Err(
+                MessageFetcherError::from_response(ProviderType::FakeMailNet, response).await,
+            );
+        }
+        let email_list_response = response.text().await?;
         let email_list: Vec<EmailListEntry> = serde_json::from_str(email_list_response.trim())
-            .map_err(|e| MessageFetcherError::FetchError(format!("Failed to parse JSON: {}", e)))?;
-
-        let mut messages = Vec::new();
-        for (i, email) in email_list.iter().rev().enumerate().skip(1) {
-            let email_content = self
-                .client
-                .get(format!("https://www.fakemail.net/email/id/{}", i + 1))
-                .header("X-Requested-With", "XMLHttpRequest")
-                .send()
-                .await?
-                .text()
-                .await?;
-            messages.push(Message {
-                from: email.from.clone(),
-                subject: email.subject.clone(),
-                body: email_content,
-            });
+            .map_err(|e| {
+                MessageFetcherError::UnexpectedResponse(
+                    ProviderType::FakeMailNet,
+                    format!("Failed to parse JSON: {}", e),
+                )
+            })?;
+
+        Ok(summaries(email_list))
+    }
+
+    async fn fetch_messages_matching(
+        &mut self,
+        filter: &MessageFilter,
+    ) -> Result<Vec<Message>, MessageFetcherError> {
+        let summaries = self.list_summaries().await?;
+        self.fetch_listed(summaries, filter).await
+    }
+
+    async fn fetch_message(&mut self, id: &str) -> Result<Message, MessageFetcherError> {
+        if let Some(message) = self.cache.get(id) {
+            return Ok(message);
         }
-        Ok(messages)
+        let summary = self
+            .list_summaries()
+            .await?
+            .into_iter()
+            .find(|summary| summary.id == id)
+            .ok_or_else(|| MessageFetcherError::MessageNotFound(id.to_string()))?;
+        let message = self.fetch_email(summary).await?;
+        self.cache.insert(message.clone());
+        Ok(message)
+    }
+
+    async fn keep_alive(&mut self) -> Result<Option<DateTime<Utc>>, MessageFetcherError> {
+        // The inbox lives as long as its session, which is kept active by checking for messages.
+        self.list_summaries().await?;
+        Ok(None)
+    }
+
+    fn get_credentials(&self) -> String {
+        export_cookies(&self.cookies, BASE_URL)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(subject: &str) -> EmailListEntry {
+        EmailListEntry {
+            subject: subject.to_string(),
+            from: "Sender <sender@example.com>".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_summary_ids() {
+        let ids = |subjects: &[&str]| -> Vec<String> {
+            summaries(subjects.iter().map(|subject| entry(subject)).collect())
+                .into_iter()
+                .map(|summary| summary.id)
+                .collect()
+        };
+        let listed = ids(&["Third", "Second", "First", "Welcome"]);
+        assert_eq!(3, listed.len());
+        assert_eq!(
+            vec!["2", "3", "4"],
+            listed.iter().map(|id| position(id)).collect::<Vec<_>>()
+        );
+
+        // After the second message is deleted, the third one takes its number but not its id.
+        let after_deletion = ids(&["Third", "First", "Welcome"]);
+        assert_eq!(listed[0], after_deletion[0]);
+        assert_eq!("3", position(&after_deletion[1]));
+        assert!(!listed.contains(&after_deletion[1]));
     }
 }
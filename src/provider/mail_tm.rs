@@ -1,39 +1,172 @@
-use std::sync::Arc;
-
-use crate::client::Client;
-use futures::lock::Mutex;
+use crate::client::{Client, StatusCode};
 use serde_json::json;
 
+use crate::capabilities::Capabilities;
+use crate::health::{probe, HealthStatus};
+use crate::name::NameRules;
+use crate::trace::SendStep;
 use crate::{
-    domain::Domain, email::EmailAddress, error::InboxCreationError, Message, MessageFetcherError,
+    domain::Domain, email::EmailAddress, error::InboxCreationError, Mailbox, Message,
+    MessageFetcherError, MessageFilter, MessageSummary,
 };
 
-use super::{Inbox, MessageFetcher, Provider};
+use super::sse::{Event, EventSource};
+use super::{
+    parse_date, Inbox, MessageCache, MessageFetcher, Provider, ProviderOptions, ProviderType,
+};
 
-pub(crate) struct MailTmProvider {}
+/// The password of all accounts, so expired sessions can sign in again.
+const PASSWORD: &str = "ephemeral_email";
+
+/// The URLs of a service with the Mail.tm API.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Backend {
+    provider_type: ProviderType,
+    api_url: String,
+    mercure_url: Option<String>,
+}
+
+impl Backend {
+    fn mail_tm() -> Self {
+        Self {
+            provider_type: ProviderType::MailTm,
+            api_url: "https://api.mail.tm".to_string(),
+            mercure_url: Some("https://mercure.mail.tm/.well-known/mercure".to_string()),
+        }
+    }
+
+    fn mail_gw() -> Self {
+        Self {
+            provider_type: ProviderType::MailGw,
+            api_url: "https://api.mail.gw".to_string(),
+            mercure_url: Some("https://mercure.mail.gw/.well-known/mercure".to_string()),
+        }
+    }
+
+    /// Returns the default backend of `provider_type`.
+    fn default_for(provider_type: ProviderType) -> Self {
+        match provider_type {
+            ProviderType::MailGw => Backend::mail_gw(),
+            _ => Backend::mail_tm(),
+        }
+    }
+
+    /// Returns the backend of `provider_type`, with the URLs overridden by `options`.
+    ///
+    /// Without a custom Mercure URL, a custom API is polled.
+    pub(crate) fn new(provider_type: ProviderType, options: &ProviderOptions) -> Self {
+        let backend = Backend::default_for(provider_type);
+        match &options.api_url {
+            Some(api_url) => Self {
+                api_url: api_url.trim_end_matches('/').to_string(),
+                mercure_url: options.mercure_url.clone(),
+                ..backend
+            },
+            None => Self {
+                mercure_url: options.mercure_url.clone().or(backend.mercure_url),
+                ..backend
+            },
+        }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}{}", self.api_url, path)
+    }
+}
+
+/// The credentials of an inbox on a backend with custom URLs, which are needed to resume it.
+///
+/// Inboxes on the default backends only store their token.
+#[derive(serde::Serialize, serde::Deserialize)]
+struct Credentials {
+    token: String,
+    api_url: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mercure_url: Option<String>,
+}
+
+impl Credentials {
+    fn encode(backend: &Backend, token: &str) -> String {
+        if *backend == Backend::default_for(backend.provider_type) {
+            return token.to_string();
+        }
+        serde_json::to_string(&Credentials {
+            token: token.to_string(),
+            api_url: backend.api_url.clone(),
+            mercure_url: backend.mercure_url.clone(),
+        })
+        .unwrap_or_else(|_| token.to_string())
+    }
+
+    /// Returns the backend and token stored in `credentials`, using `backend` for plain tokens.
+    fn decode(backend: Backend, credentials: &str) -> (Backend, String) {
+        match serde_json::from_str::<Credentials>(credentials) {
+            Ok(credentials) => (
+                Backend {
+                    api_url: credentials.api_url,
+                    mercure_url: credentials.mercure_url,
+                    ..backend
+                },
+                credentials.token,
+            ),
+            Err(_) => (backend, credentials.to_string()),
+        }
+    }
+}
+
+/// A provider for the Mail.tm API, which is also served by Mail.gw and other services.
+pub(crate) struct MailTmProvider {
+    backend: Backend,
+    options: ProviderOptions,
+}
 
 pub(crate) struct MailTmMessageFetcher {
     client: Client,
+    backend: Backend,
+    address: EmailAddress,
     token: String,
+    fetch_concurrency: usize,
+    cache: MessageCache,
+    account_id: Option<String>,
 }
 
 #[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct EmailListEntry {
     id: String,
+    from: AddressHeader,
+    subject: String,
+    size: u64,
+    created_at: String,
 }
 
 #[derive(serde::Deserialize)]
-#[allow(dead_code)]
-struct FromHeader {
+struct AddressHeader {
     address: String,
     name: String,
 }
 
+impl From<AddressHeader> for Mailbox {
+    fn from(header: AddressHeader) -> Self {
+        Mailbox::new(
+            Some(header.name),
+            Mailbox::from_header(&header.address).address,
+        )
+    }
+}
+
 #[derive(serde::Deserialize)]
+#[serde(rename_all = "camelCase")]
 struct Email {
-    from: FromHeader,
+    id: String,
+    from: AddressHeader,
+    #[serde(default)]
+    to: Vec<AddressHeader>,
+    #[serde(default)]
+    cc: Vec<AddressHeader>,
     subject: String,
     text: String,
+    created_at: String,
 }
 
 #[derive(serde::Deserialize)]
@@ -54,6 +187,11 @@ struct LoginResponse {
     token: String,
 }
 
+#[derive(serde::Deserialize)]
+struct AccountResponse {
+    id: String,
+}
+
 #[derive(serde::Deserialize)]
 #[serde(rename_all = "camelCase")]
 struct DomainResponse {
@@ -65,28 +203,69 @@ struct DomainResponse {
 impl From<Email> for crate::email::Message {
     fn from(email: Email) -> Self {
         Self {
-            from: email.from.address,
+            id: email.id,
+            from: email.from.into(),
+            to: email.to.into_iter().map(Into::into).collect(),
+            cc: email.cc.into_iter().map(Into::into).collect(),
+            reply_to: Vec::new(),
             subject: email.subject,
             body: email.text,
+            date: parse_date(&email.created_at),
+        }
+    }
+}
+
+impl From<EmailListEntry> for MessageSummary {
+    fn from(email: EmailListEntry) -> Self {
+        Self {
+            id: email.id,
+            from: email.from.into(),
+            subject: email.subject,
+            date: parse_date(&email.created_at),
+            size: Some(email.size),
         }
     }
 }
 
 impl MailTmProvider {
-    pub(crate) fn new() -> Self {
-        Self {}
+    pub(crate) const CAPABILITIES: Capabilities = Capabilities {
+        custom_name: true,
+        deletion: true,
+        resumption: true,
+        custom_api: true,
+        ..Capabilities::NONE
+    };
+
+    /// Addresses are validated as email addresses, so the local part is limited to 64
+    /// characters, and the API rejects very short names.
+    pub(crate) const NAME_RULES: NameRules = NameRules {
+        min_length: 3,
+        max_length: 64,
+        symbols: ".-_",
+        uppercase: false,
+    };
+
+    pub(crate) fn new(provider_type: ProviderType, options: ProviderOptions) -> Self {
+        Self {
+            backend: Backend::new(provider_type, &options),
+            options,
+        }
     }
 }
 
-async fn try_login(client: &Client, email: &EmailAddress) -> Result<String, InboxCreationError> {
+async fn try_login(
+    client: &Client,
+    backend: &Backend,
+    email: &EmailAddress,
+) -> Result<String, InboxCreationError> {
     let login_response = client
-        .post("https://api.mail.tm/token")
+        .post(backend.url("/token"))
         .header("ACCEPT", "application/json")
         .json(&json!({
             "address": email.to_string(),
-            "password": "ephemeral_email"
+            "password": PASSWORD
         }))
-        .send()
+        .send_step(backend.provider_type, "login")
         .await?;
 
     if login_response.status().is_success() {
@@ -94,10 +273,7 @@ async fn try_login(client: &Client, email: &EmailAddress) -> Result<String, Inbo
         return Ok(login_response.token);
     }
 
-    Err(InboxCreationError::CreationError(format!(
-        "Failed to login into: {}",
-        email
-    )))
+    Err(InboxCreationError::from_response(backend.provider_type, login_response).await)
 }
 
 #[async_trait::async_trait]
@@ -108,44 +284,44 @@ impl Provider for MailTmProvider {
     ) -> Result<Inbox, InboxCreationError> {
         let client = Client::builder().cookie_store(true).build()?;
 
-        let domain_response: Vec<DomainResponse> = client
-            .get("https://api.mail.tm/domains")
+        let provider_type = self.backend.provider_type;
+        let domain_response = client
+            .get(self.backend.url("/domains"))
             .header("ACCEPT", "application/json")
-            .send()
-            .await?
-            .json()
+            .send_step(provider_type, "domains")
             .await?;
+        if !domain_response.status().is_success() {
+            return Err(InboxCreationError::from_response(provider_type, domain_response).await);
+        }
+        let domain_response: Vec<DomainResponse> = domain_response.json().await?;
         let Some(domain) = domain_response
             .iter()
-            .filter(|d| !d.is_private && d.is_active)
-            .next()
+            .find(|d| !d.is_private && d.is_active)
         else {
-            return Err(InboxCreationError::CreationError(
+            return Err(InboxCreationError::UnexpectedResponse(
+                provider_type,
                 "No active domain found".to_string(),
             ));
         };
         let domain = Domain::from(domain.domain.as_str());
 
         let email = EmailAddress::new(name, domain);
-        if let Ok(token) = try_login(&client, &email).await {
-            return Ok(Inbox {
-                message_fetcher: Arc::new(Mutex::new(MailTmMessageFetcher { client, token })),
-                email_address: email,
-            });
+        if let Ok(token) = try_login(&client, &self.backend, &email).await {
+            return Ok(self.inbox(client, email, token));
         }
 
         let login_response = client
-            .post("https://api.mail.tm/accounts")
+            .post(self.backend.url("/accounts"))
             .header("ACCEPT", "application/json")
             .json(&json!({
                 "address": email.to_string(),
-                "password": "ephemeral_email"
+                "password": PASSWORD
             }))
-            .send()
+            .send_step(provider_type, "create_account")
             .await?;
         if !login_response.status().is_success() {
-            if login_response.status() == 429 {
-                return Err(InboxCreationError::RateLimited);
+            if login_response.status() != StatusCode::UNPROCESSABLE_ENTITY {
+                return Err(InboxCreationError::from_response(provider_type, login_response).await);
             }
             let violation: Violation = login_response.json().await?;
             if violation.status == 422 && violation.violations.len() == 1 {
@@ -162,59 +338,291 @@ impl Provider for MailTmProvider {
                 }
             }
 
-            return Err(InboxCreationError::CreationError(format!(
-                "Failed to create inbox: {}",
+            return Err(InboxCreationError::UnexpectedResponse(
+                provider_type,
                 violation
                     .violations
                     .first()
-                    .map(|v| v.message.as_str())
-                    .unwrap_or("Unknown error")
-            )));
+                    .map_or("Unknown error", |v| v.message.as_str())
+                    .to_string(),
+            ));
         }
 
-        let token = try_login(&client, &email).await?;
-        Ok(Inbox {
-            message_fetcher: Arc::new(Mutex::new(MailTmMessageFetcher { client, token })),
-            email_address: email,
+        let token = try_login(&client, &self.backend, &email).await?;
+        Ok(self.inbox(client, email, token))
+    }
+
+    async fn resume_inbox(
+        &mut self,
+        email_address: EmailAddress,
+        credentials: &str,
+    ) -> Result<Inbox, InboxCreationError> {
+        let client = Client::builder().cookie_store(true).build()?;
+        let (backend, token) = Credentials::decode(self.backend.clone(), credentials);
+        Ok(Inbox::new(
+            email_address.clone(),
+            backend.provider_type,
+            MailTmMessageFetcher::new(client, backend, email_address, token, &self.options),
+        ))
+    }
+
+    fn get_provider_type(&self) -> ProviderType {
+        self.backend.provider_type
+    }
+
+    async fn health_check(&self) -> HealthStatus {
+        let request = Client::new()
+            .get(self.backend.url("/domains"))
+            .header("ACCEPT", "application/json");
+        probe(self.backend.provider_type, request, |status, body| {
+            status.is_success() && serde_json::from_str::<Vec<DomainResponse>>(body).is_ok()
         })
+        .await
     }
+}
 
-    fn get_provider_type(&self) -> crate::provider::ProviderType {
-        crate::provider::ProviderType::MailTm
+impl MailTmProvider {
+    fn inbox(&self, client: Client, email: EmailAddress, token: String) -> Inbox {
+        Inbox::new(
+            email.clone(),
+            self.backend.provider_type,
+            MailTmMessageFetcher::new(client, self.backend.clone(), email, token, &self.options),
+        )
+    }
+}
+
+impl MailTmMessageFetcher {
+    fn new(
+        client: Client,
+        backend: Backend,
+        address: EmailAddress,
+        token: String,
+        options: &ProviderOptions,
+    ) -> Self {
+        Self {
+            client,
+            backend,
+            address,
+            token,
+            fetch_concurrency: options.fetch_concurrency,
+            cache: MessageCache::default(),
+            account_id: None,
+        }
     }
 
-    fn get_domains(&self) -> Vec<Domain> {
-        vec![]
+    async fn get_account_id(&mut self) -> Result<String, MessageFetcherError> {
+        if let Some(account_id) = &self.account_id {
+            return Ok(account_id.clone());
+        }
+        let account: AccountResponse = self.get_json("account", &self.backend.url("/me")).await?;
+        Ok(self.account_id.insert(account.id).clone())
+    }
+
+    /// Sends an authorized `GET` request as `step` and parses the JSON response.
+    async fn get_json<T: serde::de::DeserializeOwned>(
+        &self,
+        step: &'static str,
+        url: &str,
+    ) -> Result<T, MessageFetcherError> {
+        let response = self
+            .client
+            .get(url)
+            .bearer_auth(&self.token)
+            .header("ACCEPT", "application/json")
+            .send_step(self.backend.provider_type, step)
+            .await?;
+        if !response.status().is_success() {
+            return Err(
+                MessageFetcherError::from_response(self.backend.provider_type, response).await,
+            );
+        }
+        Ok(response.json().await?)
+    }
+
+    async fn fetch_email(&self, id: String) -> Result<Message, MessageFetcherError> {
+        let url = self.backend.url(&format!("/messages/{}", id));
+        match self.get_json::<Email>("message", &url).await {
+            Ok(email) => Ok(email.into()),
+            Err(error) if error.status() == Some(StatusCode::NOT_FOUND) => {
+                Err(MessageFetcherError::MessageNotFound(id))
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    async fn list_emails(&self) -> Result<Vec<EmailListEntry>, MessageFetcherError> {
+        self.get_json("messages", &self.backend.url("/messages"))
+            .await
+    }
+
+    /// Returns the listed messages that may match `filter`, fetching those not cached
+    /// concurrently.
+    ///
+    /// `email_list` must be the complete list, as messages no longer listed are removed from the
+    /// cache.
+    async fn fetch_listed(
+        &self,
+        email_list: Vec<EmailListEntry>,
+        filter: &MessageFilter,
+    ) -> Result<Vec<Message>, MessageFetcherError> {
+        self.cache.retain(
+            &email_list
+                .iter()
+                .map(|email| email.id.as_str())
+                .collect::<Vec<_>>(),
+        );
+        let summaries = email_list.into_iter().map(MessageSummary::from).collect();
+        self.cache
+            .fetch_matching(summaries, filter, self.fetch_concurrency, |summary| {
+                self.fetch_email(summary.id)
+            })
+            .await
     }
 }
 
 #[async_trait::async_trait]
 impl MessageFetcher for MailTmMessageFetcher {
     async fn fetch_messages(&mut self) -> Result<Vec<Message>, MessageFetcherError> {
-        let email_list: Vec<EmailListEntry> = self
+        let email_list = self.list_emails().await?;
+        self.fetch_listed(email_list, &MessageFilter::new()).await
+    }
+
+    async fn list_summaries(&mut self) -> Result<Vec<MessageSummary>, MessageFetcherError> {
+        Ok(self
+            .list_emails()
+            .await?
+            .into_iter()
+            .map(Into::into)
+            .collect())
+    }
+
+    async fn fetch_messages_matching(
+        &mut self,
+        filter: &MessageFilter,
+    ) -> Result<Vec<Message>, MessageFetcherError> {
+        let email_list = self.list_emails().await?;
+        self.fetch_listed(email_list, filter).await
+    }
+
+    async fn fetch_message(&mut self, id: &str) -> Result<Message, MessageFetcherError> {
+        if let Some(message) = self.cache.get(id) {
+            return Ok(message);
+        }
+        let message = self.fetch_email(id.to_string()).await?;
+        self.cache.insert(message.clone());
+        Ok(message)
+    }
+
+    async fn fetch_source(&mut self, id: &str) -> Result<Option<String>, MessageFetcherError> {
+        let response = self
             .client
-            .get("https://api.mail.tm/messages")
+            .get(self.backend.url(&format!("/messages/{}/download", id)))
             .bearer_auth(&self.token)
-            .header("ACCEPT", "application/json")
-            .send()
-            .await?
-            .json()
+            .send_step(self.backend.provider_type, "source")
+            .await?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(MessageFetcherError::MessageNotFound(id.to_string()));
+        }
+        if !response.status().is_success() {
+            return Err(
+                MessageFetcherError::from_response(self.backend.provider_type, response).await,
+            );
+        }
+        Ok(Some(response.text().await?))
+    }
+
+    fn get_credentials(&self) -> String {
+        Credentials::encode(&self.backend, &self.token)
+    }
+
+    async fn delete_inbox(&mut self) -> Result<(), MessageFetcherError> {
+        let account_id = self.get_account_id().await?;
+        let response = self
+            .client
+            .delete(self.backend.url(&format!("/accounts/{}", account_id)))
+            .bearer_auth(&self.token)
+            .send_step(self.backend.provider_type, "delete_account")
             .await?;
+        if !response.status().is_success() {
+            return Err(
+                MessageFetcherError::from_response(self.backend.provider_type, response).await,
+            );
+        }
+        Ok(())
+    }
 
-        let mut messages = Vec::new();
-        for email in email_list {
-            let email: Email = self
-                .client
-                .get(format!("https://api.mail.tm/messages/{}", email.id))
-                .bearer_auth(&self.token)
-                .header("ACCEPT", "application/json")
-                .send()
-                .await?
-                .json()
-                .await?;
-            messages.push(email.into());
-        }
-
-        Ok(messages)
+    async fn refresh_session(&mut self) -> Result<bool, InboxCreationError> {
+        // The JWT expires after a while, but the account stays valid, so a new one is requested
+        // with the fixed password.
+        self.token = try_login(&self.client, &self.backend, &self.address).await?;
+        Ok(true)
+    }
+
+    async fn event_source(&mut self) -> Option<EventSource> {
+        let mercure_url = self.backend.mercure_url.clone()?;
+        let account_id = self.get_account_id().await.ok()?;
+        Some(EventSource::new(
+            self.backend.provider_type,
+            self.client.clone(),
+            format!("{}?topic=/accounts/{}", mercure_url, account_id),
+            self.token.clone(),
+            is_message_event,
+        ))
+    }
+}
+
+/// Returns whether a Mercure update is about a message, and not for example the account quota.
+fn is_message_event(event: &Event) -> bool {
+    #[derive(serde::Deserialize)]
+    struct Update {
+        #[serde(rename = "@type")]
+        kind: String,
+    }
+
+    serde_json::from_str::<Update>(&event.data)
+        .map(|update| update.kind == "Message")
+        .unwrap_or(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backend_options() {
+        let options = ProviderOptions::default();
+        assert_eq!(
+            Backend::mail_gw(),
+            Backend::new(ProviderType::MailGw, &options)
+        );
+
+        let options = ProviderOptions {
+            api_url: Some("http://localhost:8080/".to_string()),
+            ..Default::default()
+        };
+        let backend = Backend::new(ProviderType::MailTm, &options);
+        assert_eq!("http://localhost:8080/token", backend.url("/token"));
+        assert_eq!(None, backend.mercure_url);
+    }
+
+    #[test]
+    fn test_credentials() {
+        let backend = Backend::mail_tm();
+        assert_eq!("token", Credentials::encode(&backend, "token"));
+        assert_eq!(
+            (Backend::mail_tm(), "token".to_string()),
+            Credentials::decode(Backend::mail_tm(), "token")
+        );
+
+        let custom = Backend {
+            api_url: "http://localhost:8080".to_string(),
+            mercure_url: None,
+            ..Backend::mail_tm()
+        };
+        let credentials = Credentials::encode(&custom, "token");
+        assert_eq!(
+            (custom, "token".to_string()),
+            Credentials::decode(Backend::mail_tm(), &credentials)
+        );
     }
 }
@@ -1,44 +1,73 @@
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fmt::{self, Display, Formatter};
-use std::sync::Arc;
+use std::future::Future;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::{Arc, MutexGuard, RwLock, Weak};
+use std::time::{Duration, Instant};
 
+use chrono::{DateTime, Utc};
 use futures::lock::Mutex;
+use futures::{future, stream, Stream, StreamExt, TryStreamExt};
 #[cfg(feature = "use-rquest")]
 use muellmail::MuellmailProvider;
-use rand::distr::{Alphanumeric, Distribution, SampleString, StandardUniform};
+use rand::distr::{Distribution, StandardUniform};
 use rand::seq::IndexedRandom;
+use rand::RngCore;
+use sha2::{Digest, Sha256};
 
+use crate::capabilities::Capabilities;
+use crate::catalog::DomainCatalog;
+use crate::client::{CookieStore, Jar, Url};
 use crate::domain::Domain;
-use crate::error::InboxCreationError;
-use crate::{EmailAddress, Message, MessageFetcherError};
+use crate::error::{ExportError, InboxCreationError, ProviderTypeError};
+use crate::export::{self, ExportFormat};
+use crate::health::HealthStatus;
+use crate::metrics;
+use crate::name::NameRules;
+use crate::trace::{event, span, Instrument, Span};
+use crate::{EmailAddress, Message, MessageFetcherError, MessageFilter, MessageSummary};
+use sse::EventSource;
 
 mod fakemail_net;
 mod mail_tm;
 #[cfg(feature = "use-rquest")]
 mod muellmail;
+mod sse;
 mod tempmail_lol;
 
-#[async_trait::async_trait]
-pub(crate) trait Provider: Send + Sync {
-    async fn new_random_inbox(&mut self) -> Result<Inbox, InboxCreationError> {
-        if let Ok(domain) = self.get_random_domain() {
-            return self.new_random_inbox_from_domain(domain).await;
-        }
-        self.new_random_inbox_from_name(&self.get_random_name())
-            .await
-    }
+/// Options passed from [`crate::TempMail`] to the providers and their message fetchers.
+#[derive(Debug, Clone)]
+pub(crate) struct ProviderOptions {
+    /// The maximum number of message details fetched at the same time.
+    pub(crate) fetch_concurrency: usize,
+    /// The URL of a custom API for Mail.tm-compatible providers.
+    pub(crate) api_url: Option<String>,
+    /// The URL of the Mercure hub of a custom API for Mail.tm-compatible providers.
+    pub(crate) mercure_url: Option<String>,
+}
 
-    async fn new_random_inbox_from_domain(
-        &mut self,
-        domain: Domain,
-    ) -> Result<Inbox, InboxCreationError> {
-        self.new_inbox(&self.get_random_name(), domain).await
+impl Default for ProviderOptions {
+    fn default() -> Self {
+        Self {
+            fetch_concurrency: 4,
+            api_url: None,
+            mercure_url: None,
+        }
     }
+}
 
+#[async_trait::async_trait]
+pub(crate) trait Provider: Send + Sync {
+    /// Creates an inbox on a domain chosen by the provider.
+    ///
+    /// Providers that cannot choose a domain themselves get one from [`Provider::get_random_domain`]
+    /// and are created with [`Provider::new_inbox`] instead.
     async fn new_random_inbox_from_name(
         &mut self,
-        name: &str,
+        _name: &str,
     ) -> Result<Inbox, InboxCreationError> {
-        self.new_inbox(name, self.get_random_domain()?).await
+        Err(InboxCreationError::DomainNotSupported)
     }
 
     async fn new_inbox(
@@ -49,24 +78,38 @@ pub(crate) trait Provider: Send + Sync {
         Err(InboxCreationError::ProviderNotImplemented)
     }
 
-    fn get_random_name(&self) -> String {
-        Alphanumeric
-            .sample_string(&mut rand::rng(), 8)
-            .to_ascii_lowercase()
-    }
-    fn get_random_domain(&self) -> Result<Domain, InboxCreationError> {
+    fn get_random_domain(&self, rng: &mut dyn RngCore) -> Result<Domain, InboxCreationError> {
         self.get_domains()
-            .choose(&mut rand::rng())
+            .choose(rng)
             .ok_or(InboxCreationError::DomainNotSupported)
             .cloned()
     }
     fn get_domains(&self) -> Vec<Domain> {
-        vec![]
+        if !self.capabilities().choose_domain {
+            return vec![];
+        }
+        DomainCatalog::current()
+            .domains(self.get_provider_type())
+            .to_vec()
     }
     fn get_provider_type(&self) -> ProviderType;
-    fn support_custom_domains(&self) -> bool {
-        false
+    fn capabilities(&self) -> Capabilities {
+        self.get_provider_type().capabilities()
+    }
+    fn name_rules(&self) -> NameRules {
+        self.get_provider_type().name_rules()
     }
+
+    async fn resume_inbox(
+        &mut self,
+        _email_address: EmailAddress,
+        _credentials: &str,
+    ) -> Result<Inbox, InboxCreationError> {
+        Err(InboxCreationError::ProviderNotImplemented)
+    }
+
+    /// Sends a lightweight request to the provider, without creating an inbox.
+    async fn health_check(&self) -> HealthStatus;
 }
 
 /// The type of provider to use.
@@ -77,6 +120,8 @@ pub(crate) trait Provider: Send + Sync {
 pub enum ProviderType {
     /// The FakeMail.net provider.
     FakeMailNet,
+    /// The Mail.gw provider, which serves the same API as Mail.tm.
+    MailGw,
     /// The Mail.tm provider.
     MailTm,
     /// The Muellmail.com provider.
@@ -88,30 +133,131 @@ pub enum ProviderType {
 
 impl ProviderType {
     pub(crate) fn get_provider(&self) -> Box<dyn Provider> {
+        self.get_provider_with_options(ProviderOptions::default())
+    }
+
+    pub(crate) fn get_provider_with_options(&self, options: ProviderOptions) -> Box<dyn Provider> {
         match self {
-            ProviderType::FakeMailNet => Box::new(fakemail_net::FakeMailNetProvider::new()),
-            ProviderType::MailTm => Box::new(mail_tm::MailTmProvider::new()),
+            ProviderType::FakeMailNet => Box::new(fakemail_net::FakeMailNetProvider::new(options)),
+            ProviderType::MailGw | ProviderType::MailTm => {
+                Box::new(mail_tm::MailTmProvider::new(*self, options))
+            }
             #[cfg(feature = "use-rquest")]
             ProviderType::Muellmail => Box::new(MuellmailProvider::new()),
             ProviderType::TempMailLol => Box::new(tempmail_lol::TempMailLolProvider::new()),
         }
     }
 
-    pub(crate) fn get_all_providers() -> Vec<ProviderType> {
+    /// Returns the stable identifier of the provider, as used in a [`DomainCatalog`].
+    ///
+    /// Unlike the [`Display`] name, the identifier will not change between releases.
+    ///
+    /// # Example
+    /// ```
+    /// use ephemeral_email::ProviderType;
+    ///
+    /// assert_eq!("tempmail_lol", ProviderType::TempMailLol.id());
+    /// ```
+    pub fn id(&self) -> &'static str {
+        match self {
+            ProviderType::FakeMailNet => "fakemail_net",
+            ProviderType::MailGw => "mail_gw",
+            ProviderType::MailTm => "mail_tm",
+            #[cfg(feature = "use-rquest")]
+            ProviderType::Muellmail => "muellmail",
+            ProviderType::TempMailLol => "tempmail_lol",
+        }
+    }
+
+    /// Returns the provider type with the identifier, see [`ProviderType::id`].
+    pub(crate) fn from_id(id: &str) -> Option<ProviderType> {
+        ProviderType::get_all_providers()
+            .into_iter()
+            .find(|provider_type| provider_type.id() == id)
+    }
+
+    /// Returns all provider types available with the enabled features.
+    pub fn get_all_providers() -> Vec<ProviderType> {
         vec![
             ProviderType::FakeMailNet,
+            ProviderType::MailGw,
             ProviderType::MailTm,
             #[cfg(feature = "use-rquest")]
             ProviderType::Muellmail,
             ProviderType::TempMailLol,
         ]
     }
+
+    /// Returns the features supported by the provider.
+    ///
+    /// This does not send any requests, so it can be used to select a provider up front.
+    ///
+    /// # Example
+    /// ```
+    /// use ephemeral_email::ProviderType;
+    ///
+    /// assert!(ProviderType::TempMailLol.capabilities().custom_domains);
+    /// ```
+    pub fn capabilities(&self) -> Capabilities {
+        match self {
+            ProviderType::FakeMailNet => fakemail_net::FakeMailNetProvider::CAPABILITIES,
+            ProviderType::MailGw | ProviderType::MailTm => mail_tm::MailTmProvider::CAPABILITIES,
+            #[cfg(feature = "use-rquest")]
+            ProviderType::Muellmail => muellmail::MuellmailProvider::CAPABILITIES,
+            ProviderType::TempMailLol => tempmail_lol::TempMailLolProvider::CAPABILITIES,
+        }
+    }
+
+    /// Returns the names the provider accepts, which generated names are adjusted to.
+    pub fn name_rules(&self) -> NameRules {
+        match self {
+            ProviderType::FakeMailNet => fakemail_net::FakeMailNetProvider::NAME_RULES,
+            ProviderType::MailGw | ProviderType::MailTm => mail_tm::MailTmProvider::NAME_RULES,
+            #[cfg(feature = "use-rquest")]
+            ProviderType::Muellmail => muellmail::MuellmailProvider::NAME_RULES,
+            ProviderType::TempMailLol => tempmail_lol::TempMailLolProvider::NAME_RULES,
+        }
+    }
+
+    /// Checks whether the provider is reachable and responds as expected.
+    ///
+    /// This sends a single lightweight request, like listing the available domains, and does
+    /// not create an inbox.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use ephemeral_email::ProviderType;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let status = ProviderType::MailTm.health_check().await;
+    ///     if !status.is_healthy() {
+    ///         println!("Mail.tm is {}: {:?}", status.state, status.message);
+    ///     }
+    /// }
+    /// ```
+    pub async fn health_check(&self) -> HealthStatus {
+        self.get_provider().health_check().await
+    }
+
+    /// Checks all providers concurrently, see [`ProviderType::health_check`].
+    ///
+    /// The statuses are returned in the order of [`ProviderType::get_all_providers`].
+    pub async fn health_check_all() -> Vec<HealthStatus> {
+        future::join_all(
+            ProviderType::get_all_providers()
+                .iter()
+                .map(ProviderType::health_check),
+        )
+        .await
+    }
 }
 
 impl Display for ProviderType {
     fn fmt(&self, f: &mut Formatter) -> fmt::Result {
         match self {
             ProviderType::FakeMailNet => write!(f, "FakeMail.net"),
+            ProviderType::MailGw => write!(f, "Mail.gw"),
             ProviderType::MailTm => write!(f, "Mail.tm"),
             #[cfg(feature = "use-rquest")]
             ProviderType::Muellmail => write!(f, "Muellmail"),
@@ -120,6 +266,62 @@ impl Display for ProviderType {
     }
 }
 
+/// Parses a provider from its [`ProviderType::id`] or its name, ignoring case and punctuation.
+///
+/// # Example
+/// ```
+/// use ephemeral_email::ProviderType;
+///
+/// assert_eq!(Ok(ProviderType::MailTm), "mail_tm".parse());
+/// assert_eq!(Ok(ProviderType::FakeMailNet), "fakemail.net".parse());
+/// assert!("mail.com".parse::<ProviderType>().is_err());
+/// ```
+impl FromStr for ProviderType {
+    type Err = ProviderTypeError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        fn normalize(name: &str) -> String {
+            name.chars()
+                .filter(char::is_ascii_alphanumeric)
+                .map(|c| c.to_ascii_lowercase())
+                .collect()
+        }
+
+        let name = normalize(value);
+        if let Some(provider_type) = ProviderType::get_all_providers()
+            .into_iter()
+            .find(|provider_type| normalize(provider_type.id()) == name)
+        {
+            return Ok(provider_type);
+        }
+        #[cfg(not(feature = "use-rquest"))]
+        if name == "muellmail" {
+            return Err(ProviderTypeError::FeatureNotEnabled(
+                value.to_string(),
+                "use-rquest",
+            ));
+        }
+        Err(ProviderTypeError::UnknownProvider(value.to_string()))
+    }
+}
+
+/// Serializes the provider as its [`ProviderType::id`].
+#[cfg(feature = "serde")]
+impl serde::Serialize for ProviderType {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.id())
+    }
+}
+
+/// Deserializes a provider identifier or name, see [`ProviderType::from_str`].
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for ProviderType {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let provider_type = String::deserialize(deserializer)?;
+        provider_type.parse().map_err(serde::de::Error::custom)
+    }
+}
+
 impl Distribution<ProviderType> for StandardUniform {
     fn sample<R: rand::Rng + ?Sized>(&self, rng: &mut R) -> ProviderType {
         let provider_types = ProviderType::get_all_providers();
@@ -131,6 +333,253 @@ impl Distribution<ProviderType> for StandardUniform {
 #[async_trait::async_trait]
 pub trait MessageFetcher: Send + Sync {
     async fn fetch_messages(&mut self) -> Result<Vec<Message>, MessageFetcherError>;
+
+    async fn list_summaries(&mut self) -> Result<Vec<MessageSummary>, MessageFetcherError> {
+        Ok(self
+            .fetch_messages()
+            .await?
+            .iter()
+            .map(MessageSummary::from)
+            .collect())
+    }
+
+    async fn fetch_message(&mut self, id: &str) -> Result<Message, MessageFetcherError> {
+        self.fetch_messages()
+            .await?
+            .into_iter()
+            .find(|message| message.id == id)
+            .ok_or_else(|| MessageFetcherError::MessageNotFound(id.to_string()))
+    }
+
+    /// Fetches the messages that may match `filter`.
+    ///
+    /// The inbox checks the returned messages against the filter again, so providers may leave
+    /// out conditions they cannot apply.
+    async fn fetch_messages_matching(
+        &mut self,
+        _filter: &MessageFilter,
+    ) -> Result<Vec<Message>, MessageFetcherError> {
+        self.fetch_messages().await
+    }
+
+    /// Returns the raw source of a message, if the provider offers it.
+    async fn fetch_source(&mut self, _id: &str) -> Result<Option<String>, MessageFetcherError> {
+        Ok(None)
+    }
+
+    /// Returns the provider specific credentials needed to resume the inbox.
+    fn get_credentials(&self) -> String;
+
+    async fn delete_inbox(&mut self) -> Result<(), MessageFetcherError> {
+        Err(MessageFetcherError::DeletionNotSupported)
+    }
+
+    /// Returns a stream of events signalling new messages, for providers that push them.
+    async fn event_source(&mut self) -> Option<EventSource> {
+        None
+    }
+
+    /// Extends the lifetime of the inbox, and returns when it expires now, if known.
+    async fn keep_alive(&mut self) -> Result<Option<DateTime<Utc>>, MessageFetcherError> {
+        Err(MessageFetcherError::KeepAliveNotSupported)
+    }
+
+    /// Signs in again after the provider rejected the session of the inbox.
+    ///
+    /// Returns `false` if the provider has no way to renew a session.
+    async fn refresh_session(&mut self) -> Result<bool, InboxCreationError> {
+        Ok(false)
+    }
+}
+
+/// The messages fetched by providers that need a request per message.
+#[derive(Default)]
+pub(crate) struct MessageCache {
+    messages: std::sync::Mutex<HashMap<String, Message>>,
+}
+
+impl MessageCache {
+    fn messages(&self) -> MutexGuard<'_, HashMap<String, Message>> {
+        self.messages
+            .lock()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+
+    /// Returns the cached message with `id`.
+    pub(crate) fn get(&self, id: &str) -> Option<Message> {
+        self.messages().get(id).cloned()
+    }
+
+    pub(crate) fn insert(&self, message: Message) {
+        self.messages().insert(message.id.clone(), message);
+    }
+
+    /// Removes the messages whose ids are not in `ids`.
+    pub(crate) fn retain(&self, ids: &[&str]) {
+        self.messages().retain(|id, _| ids.contains(&id.as_str()));
+    }
+
+    /// Returns the messages of `summaries` that may match `filter`, in the order of `summaries`.
+    ///
+    /// Messages that are not cached are fetched with `fetch`, at most `concurrency` at the same
+    /// time. Conditions on the body are left to the inbox.
+    pub(crate) async fn fetch_matching<F, Fut>(
+        &self,
+        summaries: Vec<MessageSummary>,
+        filter: &MessageFilter,
+        concurrency: usize,
+        fetch: F,
+    ) -> Result<Vec<Message>, MessageFetcherError>
+    where
+        F: FnMut(MessageSummary) -> Fut,
+        Fut: Future<Output = Result<Message, MessageFetcherError>>,
+    {
+        let summaries: Vec<MessageSummary> = summaries
+            .into_iter()
+            .filter(|summary| filter.may_match(summary))
+            .collect();
+        let new_summaries: Vec<MessageSummary> = {
+            let messages = self.messages();
+            summaries
+                .iter()
+                .filter(|summary| !messages.contains_key(&summary.id))
+                .cloned()
+                .collect()
+        };
+
+        let new_messages: Vec<Message> = stream::iter(new_summaries)
+            .map(fetch)
+            .buffered(concurrency)
+            .try_collect()
+            .await?;
+
+        let mut messages = self.messages();
+        messages.extend(
+            new_messages
+                .into_iter()
+                .map(|message| (message.id.clone(), message)),
+        );
+        Ok(summaries
+            .iter()
+            .filter_map(|summary| messages.get(&summary.id).cloned())
+            .collect())
+    }
+}
+
+/// Refreshes the session of `fetcher` if `error` shows that it expired.
+///
+/// Returns `Ok` if the failed call should be retried, and the error to surface otherwise.
+async fn recover_session(
+    fetcher: &mut dyn MessageFetcher,
+    error: MessageFetcherError,
+) -> Result<(), MessageFetcherError> {
+    if !error.is_auth_error() {
+        return Err(error);
+    }
+    event!(info, error = %error, "session expired, signing in again");
+    match fetcher.refresh_session().await {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(error),
+        Err(refresh_error) => {
+            event!(warn, error = %refresh_error, "session refresh failed");
+            Err(MessageFetcherError::SessionRefreshFailed(Box::new(
+                refresh_error,
+            )))
+        }
+    }
+}
+
+/// Extends the lifetime of the inbox of `fetcher`, and stores its new expiry in `expires_at`.
+async fn keep_alive(
+    fetcher: &Mutex<dyn MessageFetcher>,
+    expires_at: &RwLock<Option<DateTime<Utc>>>,
+) -> Result<(), MessageFetcherError> {
+    let mut fetcher = fetcher.lock().await;
+    let expiry = match fetcher.keep_alive().await {
+        Err(error) => {
+            recover_session(&mut *fetcher, error).await?;
+            fetcher.keep_alive().await?
+        }
+        result => result?,
+    };
+    *expires_at
+        .write()
+        .unwrap_or_else(|error| error.into_inner()) = expiry;
+    Ok(())
+}
+
+/// Parses an RFC 3339 timestamp as returned by most provider APIs.
+pub(crate) fn parse_date(date: &str) -> Option<DateTime<Utc>> {
+    DateTime::parse_from_rfc3339(date)
+        .ok()
+        .map(|date| date.with_timezone(&Utc))
+}
+
+/// Returns a hash of `fields` that is stable across platforms and releases, for providers that do
+/// not return message ids.
+pub(crate) fn content_hash(fields: &[&str]) -> u64 {
+    let mut hasher = Sha256::new();
+    for field in fields {
+        // The length keeps fields from running into each other.
+        hasher.update((field.len() as u64).to_le_bytes());
+        hasher.update(field.as_bytes());
+    }
+    let digest = hasher.finalize();
+    u64::from_be_bytes(digest[..8].try_into().expect("digest has 32 bytes"))
+}
+
+/// Returns the cookies stored in `jar` for `url` in the `Cookie` header format.
+pub(crate) fn export_cookies(jar: &Jar, url: &str) -> String {
+    Url::parse(url)
+        .ok()
+        .and_then(|url| jar.cookies(&url))
+        .and_then(|cookies| cookies.to_str().map(str::to_string).ok())
+        .unwrap_or_default()
+}
+
+/// Creates a cookie jar containing the cookies exported by [`export_cookies`].
+pub(crate) fn import_cookies(cookies: &str, url: &str) -> Arc<Jar> {
+    let jar = Jar::default();
+    if let Ok(url) = Url::parse(url) {
+        for cookie in cookies.split("; ").filter(|cookie| !cookie.is_empty()) {
+            jar.add_cookie_str(cookie, &url);
+        }
+    }
+    Arc::new(jar)
+}
+
+/// The state of an [`Inbox`] that can be stored and used to resume the inbox later.
+///
+/// With the `serde` feature, the session can be serialized, for example to store it in a file.
+///
+/// # Examples
+///
+/// ```no_run
+/// use ephemeral_email::{Inbox, InboxSession};
+///
+/// async fn example(inbox: Inbox) {
+///     let session: InboxSession = inbox.get_session().await;
+///     drop(inbox);
+///
+///     let inbox = Inbox::resume(session).await.unwrap();
+///     println!("Resumed inbox: {}", inbox.get_email_address());
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct InboxSession {
+    /// The provider the inbox was created with.
+    pub provider_type: ProviderType,
+    /// The email address of the inbox.
+    pub email_address: EmailAddress,
+    /// Provider specific credentials, like an access token or session cookies.
+    pub credentials: String,
+    /// When the provider deletes the inbox, if known, see [`Inbox::expires_at`].
+    #[cfg_attr(
+        feature = "serde",
+        serde(default, skip_serializing_if = "Option::is_none")
+    )]
+    pub expires_at: Option<DateTime<Utc>>,
 }
 
 /// Represents an email inbox with the ability to fetch messages.
@@ -138,6 +587,10 @@ pub trait MessageFetcher: Send + Sync {
 /// The `Inbox` struct holds an email address and a message fetcher that can be used to retrieve
 /// messages asynchronously.
 ///
+/// When the provider rejects the session of the inbox because it expired, the inbox signs in
+/// again and retries the request once. This is supported for Mail.tm, Mail.gw and Muellmail. If
+/// signing in fails, [`MessageFetcherError::SessionRefreshFailed`] is returned.
+///
 /// # Methods
 ///
 /// * `get_email_address` - Returns a reference to the email address associated with this inbox.
@@ -165,14 +618,179 @@ pub trait MessageFetcher: Send + Sync {
 pub struct Inbox {
     message_fetcher: Arc<Mutex<dyn MessageFetcher>>,
     email_address: EmailAddress,
+    provider_type: ProviderType,
+    returned_ids: Mutex<HashSet<String>>,
+    expires_at: Arc<RwLock<Option<DateTime<Utc>>>>,
 }
 
 impl Inbox {
+    pub(crate) fn new(
+        email_address: EmailAddress,
+        provider_type: ProviderType,
+        message_fetcher: impl MessageFetcher + 'static,
+    ) -> Self {
+        Self {
+            message_fetcher: Arc::new(Mutex::new(message_fetcher)),
+            email_address,
+            provider_type,
+            returned_ids: Mutex::new(HashSet::new()),
+            expires_at: Arc::new(RwLock::new(None)),
+        }
+    }
+
+    /// Sets when the provider deletes the inbox.
+    pub(crate) fn expiring_at(self, expires_at: Option<DateTime<Utc>>) -> Self {
+        *self
+            .expires_at
+            .write()
+            .unwrap_or_else(|error| error.into_inner()) = expires_at;
+        self
+    }
+
+    /// Resumes an inbox from a session previously returned by [`Inbox::get_session`].
+    ///
+    /// The inbox uses the default settings, use [`crate::TempMail::resume_inbox`] to configure
+    /// it like a created inbox, for example with [`crate::TempMail::fetch_concurrency`].
+    ///
+    /// Returns an [`InboxCreationError`] if the provider does not support resuming inboxes
+    /// or the session is no longer valid.
+    pub async fn resume(session: InboxSession) -> Result<Inbox, InboxCreationError> {
+        Inbox::resume_with_options(session, ProviderOptions::default()).await
+    }
+
+    /// Resumes an inbox from a session, with the fetcher configured by `options`.
+    pub(crate) async fn resume_with_options(
+        session: InboxSession,
+        options: ProviderOptions,
+    ) -> Result<Inbox, InboxCreationError> {
+        let span = span!(
+            "resume_inbox",
+            provider = %session.provider_type,
+            address = %session.email_address,
+        );
+        let inbox = session
+            .provider_type
+            .get_provider_with_options(options)
+            .resume_inbox(session.email_address, &session.credentials)
+            .instrument(span)
+            .await?;
+        Ok(inbox.expiring_at(session.expires_at))
+    }
+
+    /// Runs an `operation` fetching messages within its span, and records its metrics.
+    async fn observe<T>(
+        &self,
+        operation: &'static str,
+        future: impl Future<Output = Result<T, MessageFetcherError>>,
+    ) -> Result<T, MessageFetcherError> {
+        let start = Instant::now();
+        let result = future.instrument(self.span(operation)).await;
+        metrics::record_fetch(self.provider_type, operation, start, &result);
+        result
+    }
+
+    /// Returns the span for an operation on the inbox.
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    fn span(&self, operation: &'static str) -> Span {
+        span!(
+            "inbox",
+            operation,
+            provider = %self.provider_type,
+            address = %self.email_address,
+        )
+    }
+
     /// Returns a reference to the email address associated with this inbox.
     pub fn get_email_address(&self) -> &EmailAddress {
         &self.email_address
     }
 
+    /// Returns the type of the provider hosting this inbox.
+    pub fn get_provider_type(&self) -> ProviderType {
+        self.provider_type
+    }
+
+    /// Returns the session of this inbox, which can be stored and passed to [`Inbox::resume`] later.
+    ///
+    /// The credentials change when the session is refreshed after it expired, so the session
+    /// should be stored again after a long-running use of the inbox.
+    pub async fn get_session(&self) -> InboxSession {
+        InboxSession {
+            provider_type: self.provider_type,
+            email_address: self.email_address.clone(),
+            credentials: self.message_fetcher.lock().await.get_credentials(),
+            expires_at: self.expires_at(),
+        }
+    }
+
+    /// Returns when the provider deletes the inbox, if known.
+    ///
+    /// The expiry is taken from the responses of the provider, or from its documented lifetime
+    /// of inboxes. It is `None` for providers that keep inboxes indefinitely or do not document
+    /// their lifetime. [`Inbox::keep_alive`] updates it when the lifetime is extended.
+    pub fn expires_at(&self) -> Option<DateTime<Utc>> {
+        *self
+            .expires_at
+            .read()
+            .unwrap_or_else(|error| error.into_inner())
+    }
+
+    /// Extends the lifetime of the inbox at the provider.
+    ///
+    /// Returns [`MessageFetcherError::KeepAliveNotSupported`] if the provider does not allow
+    /// extending the lifetime of inboxes, see [`crate::Capability::KeepAlive`]. Use
+    /// [`Inbox::keep_alive_every`] to keep the inbox alive in the background.
+    pub async fn keep_alive(&self) -> Result<(), MessageFetcherError> {
+        self.observe(
+            "keep_alive",
+            keep_alive(&self.message_fetcher, &self.expires_at),
+        )
+        .await
+    }
+
+    /// Returns a future that keeps the inbox alive by calling [`Inbox::keep_alive`] every
+    /// `interval`, to be spawned on a runtime.
+    ///
+    /// The future completes once the inbox is dropped, or with an error that retrying does not
+    /// fix, like [`MessageFetcherError::KeepAliveNotSupported`].
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// use ephemeral_email::Inbox;
+    ///
+    /// async fn example(inbox: Inbox) {
+    ///     tokio::spawn(inbox.keep_alive_every(Duration::from_secs(60)));
+    ///     // The inbox stays alive while it is used, and the task stops when it is dropped.
+    /// }
+    /// ```
+    pub fn keep_alive_every(
+        &self,
+        interval: Duration,
+    ) -> impl Future<Output = Result<(), MessageFetcherError>> + Send + 'static {
+        let fetcher = Arc::downgrade(&self.message_fetcher);
+        let expires_at = self.expires_at.clone();
+        let span = self.span("keep_alive_every");
+        async move {
+            loop {
+                tokio::time::sleep(interval).await;
+                let Some(fetcher) = Weak::upgrade(&fetcher) else {
+                    return Ok(());
+                };
+                match keep_alive(&fetcher, &expires_at).await {
+                    Ok(()) => {}
+                    Err(error) if error.is_retryable() => {
+                        event!(debug, error = %error, "keeping the inbox alive failed, retrying");
+                    }
+                    Err(error) => return Err(error),
+                }
+            }
+        }
+        .instrument(span)
+    }
+
     /// Asynchronously fetches messages from the inbox.
     ///
     /// Returns a vector of [`Message`] structs. If an error occurs while fetching the messages,
@@ -196,6 +814,675 @@ impl Inbox {
     /// }
     /// ```
     pub async fn get_messages(&self) -> Result<Vec<Message>, MessageFetcherError> {
-        self.message_fetcher.lock().await.fetch_messages().await
+        self.observe("get_messages", async {
+            let mut fetcher = self.message_fetcher.lock().await;
+            match fetcher.fetch_messages().await {
+                Err(error) => {
+                    recover_session(&mut *fetcher, error).await?;
+                    fetcher.fetch_messages().await
+                }
+                result => result,
+            }
+        })
+        .await
+    }
+
+    /// Asynchronously lists the messages in the inbox without their bodies.
+    ///
+    /// For providers that need a request per message body, this only needs a single request, which
+    /// makes it the cheapest way to check whether a message has arrived. Use [`Inbox::fetch_message`]
+    /// to get the full content of a message.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ephemeral_email::Inbox;
+    ///
+    /// async fn example(inbox: Inbox) {
+    ///     let summaries = inbox.list_summaries().await.unwrap();
+    ///     if let Some(summary) = summaries.iter().find(|summary| summary.subject == "Welcome") {
+    ///         let message = inbox.fetch_message(&summary.id).await.unwrap();
+    ///         println!("Body: {}", message.body);
+    ///     }
+    /// }
+    /// ```
+    pub async fn list_summaries(&self) -> Result<Vec<MessageSummary>, MessageFetcherError> {
+        self.observe("list_summaries", async {
+            let mut fetcher = self.message_fetcher.lock().await;
+            match fetcher.list_summaries().await {
+                Err(error) => {
+                    recover_session(&mut *fetcher, error).await?;
+                    fetcher.list_summaries().await
+                }
+                result => result,
+            }
+        })
+        .await
+    }
+
+    /// Asynchronously fetches a single message by its [`Message::id`].
+    ///
+    /// Returns [`MessageFetcherError::MessageNotFound`] if the inbox contains no message with that id.
+    pub async fn fetch_message(&self, id: &str) -> Result<Message, MessageFetcherError> {
+        self.observe("fetch_message", async {
+            let mut fetcher = self.message_fetcher.lock().await;
+            match fetcher.fetch_message(id).await {
+                Err(error) => {
+                    recover_session(&mut *fetcher, error).await?;
+                    fetcher.fetch_message(id).await
+                }
+                result => result,
+            }
+        })
+        .await
+    }
+
+    /// Asynchronously fetches the raw source of a message by its [`Message::id`], in the RFC 5322
+    /// format with all headers and MIME parts.
+    ///
+    /// Returns `None` if the provider does not offer the raw source, which is currently only
+    /// available for Mail.tm.
+    pub async fn fetch_source(&self, id: &str) -> Result<Option<String>, MessageFetcherError> {
+        self.observe("fetch_source", async {
+            let mut fetcher = self.message_fetcher.lock().await;
+            match fetcher.fetch_source(id).await {
+                Err(error) => {
+                    recover_session(&mut *fetcher, error).await?;
+                    fetcher.fetch_source(id).await
+                }
+                result => result,
+            }
+        })
+        .await
+    }
+
+    /// Writes all messages of the inbox to `path` in a format standard mail clients can open.
+    ///
+    /// For [`ExportFormat::Mbox`], `path` is the file to create. For [`ExportFormat::Maildir`], it
+    /// is the directory, which is created if needed. Where the provider does not offer the raw
+    /// source of a message, its headers are synthesized from the fields of the [`Message`].
+    ///
+    /// Returns the number of exported messages.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ephemeral_email::{ExportFormat, Inbox};
+    ///
+    /// async fn example(inbox: Inbox) {
+    ///     let count = inbox.export(ExportFormat::Mbox, "inbox.mbox").await.unwrap();
+    ///     println!("Exported {} messages", count);
+    /// }
+    /// ```
+    pub async fn export(
+        &self,
+        format: ExportFormat,
+        path: impl AsRef<Path>,
+    ) -> Result<usize, ExportError> {
+        let mut messages = Vec::new();
+        for message in self.get_messages().await? {
+            let source = self.fetch_source(&message.id).await?;
+            let source = export::source(&message, source.as_deref(), &self.email_address);
+            messages.push((message, source));
+        }
+        export::write(format, path.as_ref(), &messages)?;
+        Ok(messages.len())
+    }
+
+    /// Asynchronously fetches the messages matching `filter`.
+    ///
+    /// Where the provider supports it, the filter is applied by the provider, otherwise after
+    /// fetching the messages.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ephemeral_email::{Inbox, MessageFilter};
+    ///
+    /// async fn example(inbox: Inbox) {
+    ///     let filter = MessageFilter::new().subject("Welcome");
+    ///     for message in inbox.get_messages_matching(&filter).await.unwrap() {
+    ///         println!("Body: {}", message.body);
+    ///     }
+    /// }
+    /// ```
+    pub async fn get_messages_matching(
+        &self,
+        filter: &MessageFilter,
+    ) -> Result<Vec<Message>, MessageFetcherError> {
+        let messages = self
+            .observe("get_messages_matching", async {
+                let mut fetcher = self.message_fetcher.lock().await;
+                match fetcher.fetch_messages_matching(filter).await {
+                    Err(error) => {
+                        recover_session(&mut *fetcher, error).await?;
+                        fetcher.fetch_messages_matching(filter).await
+                    }
+                    result => result,
+                }
+            })
+            .await?;
+        Ok(messages
+            .into_iter()
+            .filter(|message| filter.matches(message))
+            .collect())
+    }
+
+    /// Asynchronously fetches the messages that were not returned by a previous call of this method.
+    ///
+    /// Messages are identified by their [`Message::id`]. Providers that need a request per message
+    /// only download the messages that were not fetched before, which makes this method well suited
+    /// for polling an inbox.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use ephemeral_email::Inbox;
+    ///
+    /// async fn example(inbox: Inbox) {
+    ///     loop {
+    ///         for message in inbox.get_new_messages().await.unwrap() {
+    ///             println!("New message: {}", message.subject);
+    ///         }
+    ///         tokio::time::sleep(std::time::Duration::from_secs(5)).await;
+    ///     }
+    /// }
+    /// ```
+    pub async fn get_new_messages(&self) -> Result<Vec<Message>, MessageFetcherError> {
+        let messages = self.get_messages().await?;
+        let mut returned_ids = self.returned_ids.lock().await;
+        let new_messages: Vec<Message> = messages
+            .into_iter()
+            .filter(|message| returned_ids.insert(message.id.clone()))
+            .collect();
+        metrics::record_messages_received(self.provider_type, new_messages.len());
+        Ok(new_messages)
+    }
+
+    /// Returns a stream of the messages arriving in the inbox.
+    ///
+    /// The stream yields every message not yet returned by [`Inbox::get_new_messages`], starting with
+    /// the messages already in the inbox. For providers that push new messages, like Mail.tm, the
+    /// stream waits for these events and reconnects when the connection drops. Otherwise, or while
+    /// the push connection is unavailable, the inbox is polled every `poll_interval`.
+    ///
+    /// Errors while fetching messages are yielded, and the stream continues afterwards.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// use ephemeral_email::Inbox;
+    /// use futures::StreamExt;
+    ///
+    /// async fn example(inbox: Inbox) {
+    ///     let mut messages = Box::pin(inbox.watch(Duration::from_secs(5)));
+    ///     while let Some(message) = messages.next().await {
+    ///         match message {
+    ///             Ok(message) => println!("New message: {}", message.subject),
+    ///             Err(e) => println!("Failed to fetch messages: {:?}", e),
+    ///         }
+    ///     }
+    /// }
+    /// ```
+    pub fn watch(
+        &self,
+        poll_interval: Duration,
+    ) -> impl Stream<Item = Result<Message, MessageFetcherError>> + Send + '_ {
+        struct State {
+            pending: VecDeque<Message>,
+            event_source: Option<EventSource>,
+            started: bool,
+        }
+
+        let state = State {
+            pending: VecDeque::new(),
+            event_source: None,
+            started: false,
+        };
+        stream::unfold(state, move |mut state| async move {
+            loop {
+                if let Some(message) = state.pending.pop_front() {
+                    return Some((Ok(message), state));
+                }
+                if state.started {
+                    self.wait_for_update(&mut state.event_source, poll_interval)
+                        .await;
+                }
+                state.started = true;
+                match self.get_new_messages().await {
+                    Ok(messages) => state.pending.extend(messages),
+                    Err(error) => return Some((Err(error), state)),
+                }
+            }
+        })
+    }
+
+    /// Returns a stream of the arriving messages matching `filter`, see [`Inbox::watch`].
+    pub fn watch_matching<'a>(
+        &'a self,
+        poll_interval: Duration,
+        filter: &'a MessageFilter,
+    ) -> impl Stream<Item = Result<Message, MessageFetcherError>> + Send + 'a {
+        self.watch(poll_interval).filter(move |result| {
+            future::ready(
+                result
+                    .as_ref()
+                    .map_or(true, |message| filter.matches(message)),
+            )
+        })
+    }
+
+    /// Waits until a message matching `filter` arrives, and returns it.
+    ///
+    /// Messages already in the inbox match too, unless they were returned by
+    /// [`Inbox::get_new_messages`] or a previous call of this method. Errors that may go away, like
+    /// rate limits, are retried until `timeout` passed. Returns `None` if no message matched
+    /// within `timeout`.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    ///
+    /// use ephemeral_email::{Inbox, MessageFilter};
+    ///
+    /// async fn example(inbox: Inbox) {
+    ///     let filter = MessageFilter::new().from("noreply@example.com");
+    ///     match inbox.wait_for_message(&filter, Duration::from_secs(60)).await {
+    ///         Ok(Some(message)) => println!("Received: {}", message.subject),
+    ///         Ok(None) => println!("No message within a minute"),
+    ///         Err(e) => println!("Failed to fetch messages: {:?}", e),
+    ///     }
+    /// }
+    /// ```
+    pub async fn wait_for_message(
+        &self,
+        filter: &MessageFilter,
+        timeout: Duration,
+    ) -> Result<Option<Message>, MessageFetcherError> {
+        const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+        let wait = async {
+            let mut event_source = None;
+            loop {
+                match self.get_messages_matching(filter).await {
+                    Ok(messages) => {
+                        let mut returned_ids = self.returned_ids.lock().await;
+                        if let Some(message) = messages
+                            .into_iter()
+                            .find(|message| returned_ids.insert(message.id.clone()))
+                        {
+                            metrics::record_messages_received(self.provider_type, 1);
+                            return Ok(Some(message));
+                        }
+                    }
+                    Err(error) if error.is_retryable() => {
+                        event!(debug, error = %error, "fetching messages failed, retrying");
+                    }
+                    Err(error) => return Err(error),
+                }
+                self.wait_for_update(&mut event_source, POLL_INTERVAL).await;
+            }
+        };
+        tokio::time::timeout(timeout, wait)
+            .await
+            .unwrap_or(Ok(None))
+    }
+
+    /// Waits until the provider signals a new message, or the poll interval passed.
+    async fn wait_for_update(
+        &self,
+        event_source: &mut Option<EventSource>,
+        poll_interval: Duration,
+    ) {
+        // Even with a working push connection, the inbox is checked from time to time in case
+        // the connection silently stopped delivering events.
+        const MAX_EVENT_WAIT: Duration = Duration::from_secs(60);
+
+        if event_source.is_none() {
+            *event_source = self.message_fetcher.lock().await.event_source().await;
+        }
+        let Some(source) = event_source else {
+            tokio::time::sleep(poll_interval).await;
+            return;
+        };
+        match tokio::time::timeout(MAX_EVENT_WAIT.max(poll_interval), source.next_event()).await {
+            Ok(Ok(_)) | Err(_) => {}
+            Ok(Err(error)) => {
+                event!(debug, error = %error, "push connection failed, polling instead");
+                // The credentials of the connection expired, so it is opened again with the
+                // refreshed session.
+                if error.is_auth_error() {
+                    *event_source = None;
+                }
+                // The next call reconnects, until then fall back to polling.
+                tokio::time::sleep(poll_interval).await
+            }
+        }
+    }
+
+    /// Deletes the inbox at the provider.
+    ///
+    /// Returns [`MessageFetcherError::DeletionNotSupported`] if the provider does not support
+    /// deleting inboxes. In that case the inbox expires on its own.
+    pub async fn delete(self) -> Result<(), MessageFetcherError> {
+        self.observe("delete", async {
+            let mut fetcher = self.message_fetcher.lock().await;
+            match fetcher.delete_inbox().await {
+                Err(error) => {
+                    recover_session(&mut *fetcher, error).await?;
+                    fetcher.delete_inbox().await
+                }
+                result => result,
+            }
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    /// A fetcher whose session expires after the first request, and can be refreshed `refreshes`
+    /// times.
+    struct ExpiringFetcher {
+        expired: bool,
+        refreshes: usize,
+    }
+
+    #[async_trait::async_trait]
+    impl MessageFetcher for ExpiringFetcher {
+        async fn fetch_messages(&mut self) -> Result<Vec<Message>, MessageFetcherError> {
+            if self.expired {
+                return Err(MessageFetcherError::SessionExpired);
+            }
+            self.expired = true;
+            Ok(Vec::new())
+        }
+
+        fn get_credentials(&self) -> String {
+            self.refreshes.to_string()
+        }
+
+        async fn refresh_session(&mut self) -> Result<bool, InboxCreationError> {
+            if self.refreshes == 0 {
+                return Err(InboxCreationError::CreationError("no refresh".to_string()));
+            }
+            self.refreshes -= 1;
+            self.expired = false;
+            Ok(true)
+        }
+    }
+
+    fn inbox(refreshes: usize) -> Inbox {
+        Inbox::new(
+            "test@mail.tm".parse().unwrap(),
+            ProviderType::MailTm,
+            ExpiringFetcher {
+                expired: false,
+                refreshes,
+            },
+        )
+    }
+
+    #[tokio::test]
+    async fn test_expired_session_is_refreshed() {
+        let inbox = inbox(1);
+        assert!(inbox.get_messages().await.is_ok());
+        assert!(inbox.get_messages().await.is_ok());
+        assert!(matches!(
+            inbox.get_messages().await,
+            Err(MessageFetcherError::SessionRefreshFailed(_))
+        ));
+    }
+
+    /// A fetcher with fixed messages, which fetches the messages matching a filter one at a time
+    /// and counts the fetched messages.
+    struct StaticFetcher {
+        messages: Vec<Message>,
+        cache: MessageCache,
+        fetched: Arc<AtomicUsize>,
+    }
+
+    impl StaticFetcher {
+        fn new(messages: Vec<Message>) -> Self {
+            Self {
+                messages,
+                cache: MessageCache::default(),
+                fetched: Arc::new(AtomicUsize::new(0)),
+            }
+        }
+    }
+
+    #[async_trait::async_trait]
+    impl MessageFetcher for StaticFetcher {
+        async fn fetch_messages(&mut self) -> Result<Vec<Message>, MessageFetcherError> {
+            Ok(self.messages.clone())
+        }
+
+        async fn fetch_messages_matching(
+            &mut self,
+            filter: &MessageFilter,
+        ) -> Result<Vec<Message>, MessageFetcherError> {
+            let summaries = self.messages.iter().map(MessageSummary::from).collect();
+            let fetcher = &*self;
+            self.cache
+                .fetch_matching(summaries, filter, 2, |summary| async move {
+                    fetcher.fetched.fetch_add(1, Ordering::SeqCst);
+                    fetcher
+                        .messages
+                        .iter()
+                        .find(|message| message.id == summary.id)
+                        .cloned()
+                        .ok_or(MessageFetcherError::MessageNotFound(summary.id))
+                })
+                .await
+        }
+
+        fn get_credentials(&self) -> String {
+            String::new()
+        }
+    }
+
+    fn message(id: &str, subject: &str) -> Message {
+        Message {
+            id: id.to_string(),
+            from: "sender@example.com".parse().unwrap(),
+            to: Vec::new(),
+            cc: Vec::new(),
+            reply_to: Vec::new(),
+            subject: subject.to_string(),
+            body: format!("Body of {}", id),
+            date: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_messages_matching() {
+        let inbox = Inbox::new(
+            "test@mail.tm".parse().unwrap(),
+            ProviderType::MailTm,
+            StaticFetcher::new(vec![message("1", "Welcome"), message("2", "Your code")]),
+        );
+        let filter = MessageFilter::new().subject("code");
+        let messages = inbox.get_messages_matching(&filter).await.unwrap();
+        assert_eq!(
+            vec!["2"],
+            messages.iter().map(|m| &m.id).collect::<Vec<_>>()
+        );
+
+        let filter = MessageFilter::new().body("of 1");
+        let messages = inbox.get_messages_matching(&filter).await.unwrap();
+        assert_eq!(
+            vec!["1"],
+            messages.iter().map(|m| &m.id).collect::<Vec<_>>()
+        );
+
+        let filter = MessageFilter::new();
+        let first = inbox.wait_for_message(&filter, Duration::from_secs(1));
+        assert_eq!("1", first.await.unwrap().unwrap().id);
+        let second = inbox.wait_for_message(&filter, Duration::from_secs(1));
+        assert_eq!("2", second.await.unwrap().unwrap().id);
+    }
+
+    #[tokio::test]
+    async fn test_only_matching_summaries_are_fetched() {
+        let fetcher = StaticFetcher::new(vec![message("1", "Welcome"), message("2", "Your code")]);
+        let fetched = fetcher.fetched.clone();
+        let inbox = Inbox::new(
+            "test@mail.tm".parse().unwrap(),
+            ProviderType::MailTm,
+            fetcher,
+        );
+
+        let filter = MessageFilter::new().subject("code");
+        let messages = inbox.get_messages_matching(&filter).await.unwrap();
+        assert_eq!(
+            vec!["2"],
+            messages.iter().map(|m| &m.id).collect::<Vec<_>>()
+        );
+        assert_eq!(1, fetched.load(Ordering::SeqCst));
+
+        // The cached message is not fetched again, the body is checked by the inbox.
+        let filter = MessageFilter::new().body("of 1");
+        let messages = inbox.get_messages_matching(&filter).await.unwrap();
+        assert_eq!(
+            vec!["1"],
+            messages.iter().map(|m| &m.id).collect::<Vec<_>>()
+        );
+        assert_eq!(2, fetched.load(Ordering::SeqCst));
+        let messages = inbox
+            .get_messages_matching(&MessageFilter::new())
+            .await
+            .unwrap();
+        assert_eq!(2, messages.len());
+        assert_eq!(2, fetched.load(Ordering::SeqCst));
+    }
+
+    #[test]
+    fn test_message_cache_retain() {
+        let cache = MessageCache::default();
+        cache.insert(message("1", "Welcome"));
+        cache.insert(message("2", "Your code"));
+        cache.retain(&["2"]);
+        assert!(cache.get("1").is_none());
+        assert_eq!("Your code", cache.get("2").unwrap().subject);
+    }
+
+    /// A fetcher whose messages can be changed by the test.
+    struct SharedFetcher(Arc<std::sync::Mutex<Vec<Message>>>);
+
+    #[async_trait::async_trait]
+    impl MessageFetcher for SharedFetcher {
+        async fn fetch_messages(&mut self) -> Result<Vec<Message>, MessageFetcherError> {
+            Ok(self.0.lock().unwrap().clone())
+        }
+
+        fn get_credentials(&self) -> String {
+            String::new()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_get_new_messages() {
+        let messages = Arc::new(std::sync::Mutex::new(vec![message("1", "Welcome")]));
+        let inbox = Inbox::new(
+            "test@mail.tm".parse().unwrap(),
+            ProviderType::MailTm,
+            SharedFetcher(messages.clone()),
+        );
+        let ids = |messages: Vec<Message>| -> Vec<String> {
+            messages.into_iter().map(|message| message.id).collect()
+        };
+        assert_eq!(vec!["1"], ids(inbox.get_new_messages().await.unwrap()));
+        assert!(inbox.get_new_messages().await.unwrap().is_empty());
+
+        messages.lock().unwrap().push(message("2", "Your code"));
+        assert_eq!(vec!["2"], ids(inbox.get_new_messages().await.unwrap()));
+        assert_eq!(2, inbox.get_messages().await.unwrap().len());
+        assert!(inbox.get_new_messages().await.unwrap().is_empty());
+    }
+
+    /// A fetcher whose inbox lives for another hour after each keep alive.
+    struct KeepAliveFetcher;
+
+    #[async_trait::async_trait]
+    impl MessageFetcher for KeepAliveFetcher {
+        async fn fetch_messages(&mut self) -> Result<Vec<Message>, MessageFetcherError> {
+            Ok(Vec::new())
+        }
+
+        fn get_credentials(&self) -> String {
+            String::new()
+        }
+
+        async fn keep_alive(&mut self) -> Result<Option<DateTime<Utc>>, MessageFetcherError> {
+            Ok(Some(
+                DateTime::<Utc>::from(std::time::SystemTime::now()) + chrono::TimeDelta::hours(1),
+            ))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_keep_alive() {
+        let inbox = Inbox::new(
+            "test@mail.tm".parse().unwrap(),
+            ProviderType::MailTm,
+            KeepAliveFetcher,
+        );
+        assert_eq!(None, inbox.expires_at());
+        inbox.keep_alive().await.unwrap();
+        let expires_at = inbox.expires_at().unwrap();
+        assert_eq!(Some(expires_at), inbox.get_session().await.expires_at);
+
+        let task = tokio::spawn(inbox.keep_alive_every(Duration::from_millis(10)));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert!(inbox.expires_at().unwrap() > expires_at);
+        drop(inbox);
+        assert!(task.await.unwrap().is_ok());
+
+        let inbox = self::inbox(0);
+        assert!(matches!(
+            inbox.keep_alive().await,
+            Err(MessageFetcherError::KeepAliveNotSupported)
+        ));
+    }
+
+    #[test]
+    fn test_provider_type_from_str() {
+        for provider_type in ProviderType::get_all_providers() {
+            assert_eq!(Ok(provider_type), provider_type.id().parse());
+            assert_eq!(Ok(provider_type), provider_type.to_string().parse());
+        }
+        assert_eq!(
+            Err(ProviderTypeError::UnknownProvider("mail.com".to_string())),
+            "mail.com".parse::<ProviderType>()
+        );
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_session_serde() {
+        let session = InboxSession {
+            provider_type: ProviderType::TempMailLol,
+            email_address: "test@custom.com".parse().unwrap(),
+            credentials: "token".to_string(),
+            expires_at: None,
+        };
+        let json = serde_json::to_value(&session).unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({
+                "provider_type": "tempmail_lol",
+                "email_address": "test@custom.com",
+                "credentials": "token",
+            })
+        );
+        assert_eq!(session, serde_json::from_value(json).unwrap());
     }
 }
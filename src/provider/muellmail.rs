@@ -1,19 +1,31 @@
 use std::sync::Arc;
+use std::time::SystemTime;
 
+use crate::capabilities::Capabilities;
 use crate::domain::Domain;
 use crate::email::EmailAddress;
 use crate::error::{InboxCreationError, MessageFetcherError};
-use crate::Message;
-use futures::lock::Mutex;
+use crate::health::{probe, HealthStatus};
+use crate::name::NameRules;
+use crate::trace::SendStep;
+use crate::{Mailbox, Message, MessageFilter, MessageSummary};
+use chrono::{DateTime, TimeDelta, Utc};
+use rquest::cookie::Jar;
 use rquest::Client;
 use rquest_util::Emulation;
 
-use super::{Inbox, MessageFetcher, Provider};
+use super::{
+    export_cookies, import_cookies, parse_date, Inbox, MessageFetcher, Provider, ProviderType,
+};
+
+const BASE_URL: &str = "https://muellmail.com/";
 
 pub(crate) struct MuellmailProvider {}
 
 pub(crate) struct MuellmailMessageFetcher {
     client: Client,
+    cookies: Arc<Jar>,
+    email: EmailAddress,
 }
 
 #[derive(serde::Deserialize)]
@@ -54,132 +66,242 @@ struct Email {
     size_in_bytes: i64,
 }
 
-impl From<Email> for crate::email::Message {
-    fn from(email: Email) -> Self {
-        Self {
-            from: email.sender,
-            subject: email.subject,
-            body: email.text.or(email.html).unwrap_or_default(),
+impl Email {
+    fn sender(&self) -> Mailbox {
+        Mailbox::new(
+            Some(self.sender_name.clone()),
+            Mailbox::from_header(&self.sender).address,
+        )
+    }
+
+    fn summary(&self) -> MessageSummary {
+        MessageSummary {
+            id: self.id.clone(),
+            from: self.sender(),
+            subject: self.subject.clone(),
+            date: parse_date(&self.created_at),
+            size: u64::try_from(self.size_in_bytes).ok(),
+        }
+    }
+
+    fn into_message(self, recipient: &EmailAddress) -> Message {
+        let from = self.sender();
+        Message {
+            id: self.id,
+            from,
+            to: vec![recipient.clone().into()],
+            cc: Vec::new(),
+            reply_to: Vec::new(),
+            subject: self.subject,
+            body: self.text.or(self.html).unwrap_or_default(),
+            date: parse_date(&self.created_at),
         }
     }
 }
 
 impl MuellmailProvider {
+    pub(crate) const CAPABILITIES: Capabilities = Capabilities {
+        custom_name: true,
+        choose_domain: true,
+        resumption: true,
+        ..Capabilities::NONE
+    };
+
+    /// Any name works, so only the limit of the local part of email addresses applies.
+    pub(crate) const NAME_RULES: NameRules = NameRules {
+        min_length: 1,
+        max_length: 64,
+        symbols: ".-_",
+        uppercase: false,
+    };
+
     pub fn new() -> Self {
         Self {}
     }
 }
 
+/// Signs in to the inbox of `email` anonymously, which stores the session in the cookies of
+/// `client`.
+async fn sign_in(client: &Client, email: &EmailAddress) -> Result<(), InboxCreationError> {
+    let response = client
+        .get("https://muellmail.com/api/auth/csrf")
+        .send_step(ProviderType::Muellmail, "csrf")
+        .await?;
+    if !response.status().is_success() {
+        return Err(InboxCreationError::from_response(ProviderType::Muellmail, response).await);
+    }
+    let csrf_token: CsrfResponse = response.json().await?;
+
+    let response = client
+        .post("https://muellmail.com/api/auth/callback/anon")
+        .form(&[
+            ("redirect", "false"),
+            ("muellmail", &email.to_string()),
+            ("csrfToken", &csrf_token.csrf_token),
+            ("callbackUrl", "https://muellmail.com/en"),
+            ("json", "true"),
+        ])
+        .send_step(ProviderType::Muellmail, "sign_in")
+        .await?;
+    if !response.status().is_success() {
+        return Err(InboxCreationError::from_response(ProviderType::Muellmail, response).await);
+    }
+    let anon: AnonResponse = response.json().await?;
+
+    if anon.url != "https://muellmail.com/en" {
+        return Err(InboxCreationError::UnexpectedResponse(
+            ProviderType::Muellmail,
+            format!("Invalid response url {}", anon.url),
+        ));
+    }
+    Ok(())
+}
+
 #[async_trait::async_trait]
 impl Provider for MuellmailProvider {
     async fn new_inbox(&mut self, name: &str, domain: Domain) -> Result<Inbox, InboxCreationError> {
+        // Inboxes on the 10minutenmail.xyz domain are deleted after ten minutes, the others are
+        // kept without a documented limit.
+        let expires_at = matches!(domain, Domain::TenMinutenMailXyz)
+            .then(|| DateTime::<Utc>::from(SystemTime::now()) + TimeDelta::minutes(10));
         let email = EmailAddress::new(name, domain);
+        let cookies = Arc::new(Jar::default());
         let client = Client::builder()
-            .cookie_store(true)
+            .cookie_provider(cookies.clone())
             .emulation(Emulation::Firefox135)
             .build()?;
 
-        let csrf_token: CsrfResponse = client
-            .get("https://muellmail.com/api/auth/csrf")
-            .send()
-            .await?
-            .json()
-            .await?;
+        sign_in(&client, &email).await?;
 
-        let anon: AnonResponse = client
-            .post("https://muellmail.com/api/auth/callback/anon")
-            .form(&[
-                ("redirect", "false"),
-                ("muellmail", &email.to_string()),
-                ("csrfToken", &csrf_token.csrf_token),
-                ("callbackUrl", "https://muellmail.com/en"),
-                ("json", "true"),
-            ])
-            .send()
-            .await?
-            .json()
-            .await?;
+        Ok(Inbox::new(
+            email.clone(),
+            ProviderType::Muellmail,
+            MuellmailMessageFetcher {
+                client,
+                cookies,
+                email,
+            },
+        )
+        .expiring_at(expires_at))
+    }
 
-        if anon.url != "https://muellmail.com/en" {
-            return Err(InboxCreationError::CreationError(format!(
-                "Invalid response url {}",
-                anon.url
-            )));
-        }
+    async fn resume_inbox(
+        &mut self,
+        email_address: EmailAddress,
+        credentials: &str,
+    ) -> Result<Inbox, InboxCreationError> {
+        let cookies = import_cookies(credentials, BASE_URL);
+        let client = Client::builder()
+            .cookie_provider(cookies.clone())
+            .emulation(Emulation::Firefox135)
+            .build()?;
+        Ok(Inbox::new(
+            email_address.clone(),
+            ProviderType::Muellmail,
+            MuellmailMessageFetcher {
+                client,
+                cookies,
+                email: email_address,
+            },
+        ))
+    }
 
-        Ok(Inbox {
-            message_fetcher: Arc::new(Mutex::new(MuellmailMessageFetcher { client })),
-            email_address: email,
-        })
-    }
-
-    fn get_provider_type(&self) -> crate::provider::ProviderType {
-        crate::provider::ProviderType::Muellmail
-    }
-
-    fn get_domains(&self) -> Vec<Domain> {
-        vec![
-            Domain::TenMinMailDe,
-            Domain::TenMinutenMailXyz,
-            Domain::ExistiertNet,
-            Domain::FliegenderFish,
-            Domain::JagaEmail,
-            Domain::MdzEmail,
-            Domain::MuellMailCom,
-            Domain::MuelleMailCom,
-            Domain::MuellMonster,
-            Domain::MuellIcu,
-            Domain::MuellIo,
-            Domain::MuellXyz,
-            Domain::MagSpamNet,
-            Domain::FukaruCom,
-            Domain::OidaIcu,
-            Domain::PapierkorbMe,
-            Domain::SpamCare,
-            Domain::TonneTo,
-            Domain::UltraFyi,
-            Domain::WegwerfEmailDe,
-            Domain::DsgvoParty,
-            Domain::KnickerbockerbanDe,
-            Domain::LambsauceDe,
-            Domain::RamenMailDe,
-            Domain::Ji5De,
-            Domain::Ji6De,
-            Domain::Ji7De,
-            Domain::SudernDe,
-            Domain::HihiLol,
-            Domain::KeinDate,
-            Domain::HolioDay,
-            Domain::CornHolioDay,
-            Domain::BungHolioDay,
-            Domain::StacysMom,
-        ]
+    fn get_provider_type(&self) -> ProviderType {
+        ProviderType::Muellmail
+    }
+
+    async fn health_check(&self) -> HealthStatus {
+        let client = match Client::builder().emulation(Emulation::Firefox135).build() {
+            Ok(client) => client,
+            Err(error) => return HealthStatus::unreachable(ProviderType::Muellmail, error),
+        };
+        probe(
+            ProviderType::Muellmail,
+            client.get("https://muellmail.com/api/auth/csrf"),
+            |status, body| {
+                status.is_success() && serde_json::from_str::<CsrfResponse>(body).is_ok()
+            },
+        )
+        .await
     }
 }
 
-#[async_trait::async_trait]
-impl MessageFetcher for MuellmailMessageFetcher {
-    async fn fetch_messages(&mut self) -> Result<Vec<Message>, MessageFetcherError> {
+/// Returns the GraphQL `where` clause for the conditions of `filter` the API can check, or `None`
+/// if there are none.
+///
+/// Regular expressions and conditions on the body are only checked by the inbox.
+fn where_clause(filter: &MessageFilter) -> Option<String> {
+    // JSON strings are valid GraphQL strings.
+    let contains = |text: &str| {
+        format!(
+            "{{contains: {}, mode: insensitive}}",
+            serde_json::Value::from(text)
+        )
+    };
+
+    let mut conditions = Vec::new();
+    if let Some(from) = &filter.from {
+        conditions.push(format!(
+            "OR: [{{sender: {0}}}, {{senderName: {0}}}]",
+            contains(from)
+        ));
+    }
+    if let Some(subject) = &filter.subject {
+        conditions.push(format!("subject: {}", contains(subject)));
+    }
+    let mut created_at = Vec::new();
+    if let Some(since) = filter.since {
+        created_at.push(format!("gte: \"{}\"", since.to_rfc3339()));
+    }
+    if let Some(until) = filter.until {
+        created_at.push(format!("lte: \"{}\"", until.to_rfc3339()));
+    }
+    if !created_at.is_empty() {
+        conditions.push(format!("createdAt: {{{}}}", created_at.join(", ")));
+    }
+    if conditions.is_empty() {
+        return None;
+    }
+    Some(format!("{{{}}}", conditions.join(", ")))
+}
+
+impl MuellmailMessageFetcher {
+    /// Queries the messages matching the GraphQL `where_clause`, or all messages, newest first.
+    async fn query_emails(
+        &self,
+        where_clause: Option<String>,
+    ) -> Result<Vec<Email>, MessageFetcherError> {
+        let arguments = match where_clause {
+            Some(where_clause) => format!("where: {}, orderBy: {{createdAt: desc}}", where_clause),
+            None => "orderBy: {createdAt: desc}".to_string(),
+        };
         let session_response = self
             .client
             .get("https://muellmail.com/api/auth/session")
-            .send()
+            .send_step(ProviderType::Muellmail, "session")
             .await?;
         if session_response.status() != 200 {
-            return Err(MessageFetcherError::InvalidResponseStatus(
-                session_response.status(),
-            ));
+            return Err(MessageFetcherError::from_response(
+                ProviderType::Muellmail,
+                session_response,
+            )
+            .await);
+        }
+        // An expired session is answered with an empty object instead of an error status.
+        let session: serde_json::Value = session_response.json().await?;
+        if session.as_object().is_none_or(|session| session.is_empty()) {
+            return Err(MessageFetcherError::SessionExpired);
         }
 
-        let message_query_response: MessageQueryResponse = self
+        let response = self
             .client
             .post("https://muellmail.com/graphql")
             .json(&serde_json::json!({
                 "operationName": "MailQuery",
                 "variables": {},
-                "query": r#"
-                    query MailQuery {
-                        emails(orderBy: {createdAt: desc}) {
+                "query": format!(r#"
+                    query MailQuery {{
+                        emails({}) {{
                             id
                             subject
                             sender
@@ -191,7 +313,7 @@ impl MessageFetcher for MuellmailMessageFetcher {
                             html
                             text
                             sizeInBytes
-                            attachments {
+                            attachments {{
                                 id
                                 emailId
                                 fileName
@@ -201,22 +323,85 @@ impl MessageFetcher for MuellmailMessageFetcher {
                                 createdAt
                                 updatedAt
                                 __typename
-                            }
+                            }}
                             __typename
-                        }
-                    }
-                "#
+                        }}
+                    }}
+                "#, arguments)
             }))
-            .send()
-            .await?
-            .json()
+            .send_step(ProviderType::Muellmail, "messages")
             .await?;
+        if !response.status().is_success() {
+            return Err(
+                MessageFetcherError::from_response(ProviderType::Muellmail, response).await,
+            );
+        }
+        let message_query_response: MessageQueryResponse = response.json().await?;
+
+        Ok(message_query_response.data.emails)
+    }
 
-        Ok(message_query_response
-            .data
-            .emails
+    /// Converts the queried emails into messages of this inbox.
+    fn messages(&self, emails: Vec<Email>) -> Vec<Message> {
+        emails
             .into_iter()
-            .map(Into::into)
-            .collect())
+            .map(|email| email.into_message(&self.email))
+            .collect()
+    }
+}
+
+#[async_trait::async_trait]
+impl MessageFetcher for MuellmailMessageFetcher {
+    async fn fetch_messages(&mut self) -> Result<Vec<Message>, MessageFetcherError> {
+        let emails = self.query_emails(None).await?;
+        Ok(self.messages(emails))
+    }
+
+    async fn list_summaries(&mut self) -> Result<Vec<MessageSummary>, MessageFetcherError> {
+        let emails = self.query_emails(None).await?;
+        Ok(emails.iter().map(Email::summary).collect())
+    }
+
+    async fn fetch_messages_matching(
+        &mut self,
+        filter: &MessageFilter,
+    ) -> Result<Vec<Message>, MessageFetcherError> {
+        let emails = self.query_emails(where_clause(filter)).await?;
+        Ok(self.messages(emails))
+    }
+
+    fn get_credentials(&self) -> String {
+        export_cookies(&self.cookies, BASE_URL)
+    }
+
+    async fn refresh_session(&mut self) -> Result<bool, InboxCreationError> {
+        sign_in(&self.client, &self.email).await?;
+        Ok(true)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_where_clause() {
+        assert_eq!(None, where_clause(&MessageFilter::new()));
+        assert_eq!(None, where_clause(&MessageFilter::new().body("code")));
+
+        let since = chrono::DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let filter = MessageFilter::new()
+            .from("Shop")
+            .subject(r#"Say "hi""#)
+            .since(since);
+        assert_eq!(
+            Some(concat!(
+                r#"{OR: [{sender: {contains: "Shop", mode: insensitive}}, "#,
+                r#"{senderName: {contains: "Shop", mode: insensitive}}], "#,
+                r#"subject: {contains: "Say \"hi\"", mode: insensitive}, "#,
+                r#"createdAt: {gte: "2023-11-14T22:13:20+00:00"}}"#,
+            )),
+            where_clause(&filter).as_deref()
+        );
     }
 }
@@ -0,0 +1,263 @@
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use crate::client::{Client, Response};
+use crate::trace::SendStep;
+use crate::MessageFetcherError;
+
+use super::ProviderType;
+
+/// The delay before reconnecting, until the server sets another one with the `retry` field.
+const DEFAULT_RETRY: Duration = Duration::from_secs(1);
+
+/// The maximum delay before reconnecting after repeated failures.
+const MAX_RETRY: Duration = Duration::from_secs(60);
+
+/// An event received from a server-sent events stream.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Event {
+    pub(crate) id: Option<String>,
+    pub(crate) data: String,
+}
+
+/// An incremental parser for the `text/event-stream` format.
+#[derive(Debug, Default)]
+struct Parser {
+    buffer: Vec<u8>,
+    id: Option<String>,
+    data: Option<String>,
+    retry: Option<Duration>,
+}
+
+impl Parser {
+    /// Feeds a chunk of the stream and returns the events completed by it.
+    fn feed(&mut self, chunk: &[u8]) -> Vec<Event> {
+        self.buffer.extend_from_slice(chunk);
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.iter().position(|&byte| byte == b'\n') {
+            let line: Vec<u8> = self.buffer.drain(..=end).collect();
+            let line = String::from_utf8_lossy(&line);
+            let line = line.trim_end_matches(['\n', '\r']);
+            if line.is_empty() {
+                if let Some(data) = self.data.take() {
+                    events.push(Event {
+                        id: self.id.clone(),
+                        data,
+                    });
+                }
+                continue;
+            }
+
+            let (field, value) = line.split_once(':').unwrap_or((line, ""));
+            let value = value.strip_prefix(' ').unwrap_or(value);
+            match field {
+                "data" => match &mut self.data {
+                    Some(data) => {
+                        data.push('\n');
+                        data.push_str(value);
+                    }
+                    None => self.data = Some(value.to_string()),
+                },
+                "id" => self.id = Some(value.to_string()),
+                "retry" => {
+                    if let Ok(millis) = value.parse() {
+                        self.retry = Some(Duration::from_millis(millis));
+                    }
+                }
+                // Comments, used as heartbeats, and other fields are ignored.
+                _ => {}
+            }
+        }
+        events
+    }
+}
+
+/// A connection to a server-sent events endpoint, which reconnects when the stream ends.
+///
+/// When reconnecting, the id of the last received event is sent in the `Last-Event-ID` header,
+/// so the server can replay the events missed in between. Only events accepted by the filter
+/// are returned.
+///
+/// Reconnecting waits for the delay set by the server with the `retry` field, which doubles
+/// with every failed or closed connection in a row.
+pub(crate) struct EventSource {
+    provider_type: ProviderType,
+    client: Client,
+    url: String,
+    token: String,
+    filter: fn(&Event) -> bool,
+    last_event_id: Option<String>,
+    response: Option<Response>,
+    parser: Parser,
+    events: VecDeque<Event>,
+    retry: Duration,
+    failures: u32,
+}
+
+impl EventSource {
+    pub(crate) fn new(
+        provider_type: ProviderType,
+        client: Client,
+        url: String,
+        token: String,
+        filter: fn(&Event) -> bool,
+    ) -> Self {
+        Self {
+            provider_type,
+            client,
+            url,
+            token,
+            filter,
+            last_event_id: None,
+            response: None,
+            parser: Parser::default(),
+            events: VecDeque::new(),
+            retry: DEFAULT_RETRY,
+            failures: 0,
+        }
+    }
+
+    /// Waits for the next event, connecting to the endpoint if needed.
+    ///
+    /// After an error the next call reconnects.
+    pub(crate) async fn next_event(&mut self) -> Result<Event, MessageFetcherError> {
+        loop {
+            if let Some(event) = self.events.pop_front() {
+                if event.id.is_some() {
+                    self.last_event_id.clone_from(&event.id);
+                }
+                if (self.filter)(&event) {
+                    return Ok(event);
+                }
+                continue;
+            }
+
+            if self.response.is_none() {
+                if self.failures > 0 {
+                    tokio::time::sleep(self.reconnect_delay()).await;
+                }
+                match self.connect().await {
+                    Ok(response) => self.response = Some(response),
+                    Err(error) => {
+                        self.failures += 1;
+                        return Err(error);
+                    }
+                }
+            }
+            let Some(response) = &mut self.response else {
+                continue;
+            };
+            match response.chunk().await {
+                Ok(Some(chunk)) => {
+                    let events = self.parser.feed(&chunk);
+                    if let Some(retry) = self.parser.retry.take() {
+                        self.retry = retry;
+                    }
+                    if !events.is_empty() {
+                        self.failures = 0;
+                    }
+                    self.events.extend(events);
+                }
+                Ok(None) => {
+                    self.disconnect();
+                }
+                Err(error) => {
+                    self.disconnect();
+                    return Err(error.into());
+                }
+            }
+        }
+    }
+
+    /// Drops the connection, so the next call reconnects after a delay.
+    fn disconnect(&mut self) {
+        self.response = None;
+        self.parser = Parser::default();
+        self.failures += 1;
+    }
+
+    /// Returns the delay before reconnecting, doubled for every failure in a row.
+    fn reconnect_delay(&self) -> Duration {
+        let factor = 2u32.saturating_pow(self.failures.saturating_sub(1));
+        self.retry.saturating_mul(factor).min(MAX_RETRY)
+    }
+
+    async fn connect(&self) -> Result<Response, MessageFetcherError> {
+        let mut request = self
+            .client
+            .get(&self.url)
+            .bearer_auth(&self.token)
+            .header("ACCEPT", "text/event-stream");
+        if let Some(last_event_id) = &self.last_event_id {
+            request = request.header("Last-Event-ID", last_event_id);
+        }
+        let response = request.send_step(self.provider_type, "events").await?;
+        if !response.status().is_success() {
+            return Err(MessageFetcherError::from_response(self.provider_type, response).await);
+        }
+        Ok(response)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parser_events() {
+        let mut parser = Parser::default();
+        let events = parser
+            .feed(b": heartbeat\n\nid: 1\ndata: {\"a\":\ndata: 1}\n\nid: 2\r\ndata:x\r\n\r\n");
+        assert_eq!(
+            events,
+            vec![
+                Event {
+                    id: Some("1".to_string()),
+                    data: "{\"a\":\n1}".to_string(),
+                },
+                Event {
+                    id: Some("2".to_string()),
+                    data: "x".to_string(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parser_split_chunks() {
+        let mut parser = Parser::default();
+        assert!(parser.feed(b"id: 7\nda").is_empty());
+        assert!(parser.feed(b"ta: hello\n").is_empty());
+        assert_eq!(
+            parser.feed(b"\n"),
+            vec![Event {
+                id: Some("7".to_string()),
+                data: "hello".to_string(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parser_retry() {
+        let mut parser = Parser::default();
+        assert!(parser.feed(b"retry: 2500\n\nretry: soon\n\n").is_empty());
+        assert_eq!(Some(Duration::from_millis(2500)), parser.retry);
+    }
+
+    #[test]
+    fn test_reconnect_delay() {
+        let mut source = EventSource::new(
+            ProviderType::MailTm,
+            Client::new(),
+            "https://example.com".to_string(),
+            String::new(),
+            |_| true,
+        );
+        source.failures = 1;
+        assert_eq!(DEFAULT_RETRY, source.reconnect_delay());
+        source.failures = 3;
+        assert_eq!(DEFAULT_RETRY * 4, source.reconnect_delay());
+        source.failures = 40;
+        assert_eq!(MAX_RETRY, source.reconnect_delay());
+    }
+}
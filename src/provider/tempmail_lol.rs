@@ -1,12 +1,19 @@
-use std::sync::Arc;
+use std::time::SystemTime;
 
+use crate::capabilities::Capabilities;
 use crate::client::Client;
-use futures::lock::Mutex;
+use crate::health::{probe, HealthStatus};
+use crate::name::NameRules;
+use crate::trace::SendStep;
+use chrono::{DateTime, TimeDelta, Utc};
 use serde_json::json;
 
-use crate::{Domain, InboxCreationError, Message, MessageFetcherError};
+use crate::{Domain, EmailAddress, InboxCreationError, Mailbox, Message, MessageFetcherError};
 
-use super::{Inbox, MessageFetcher, Provider, ProviderType};
+use super::{content_hash, Inbox, MessageFetcher, Provider, ProviderType};
+
+/// The lifetime of inboxes on the free plan, after which they are deleted.
+const INBOX_LIFETIME: TimeDelta = TimeDelta::hours(1);
 
 pub(crate) struct TempMailLolProvider {}
 
@@ -16,18 +23,74 @@ pub(crate) struct TempMailLolMessageFetcher {
 }
 
 impl TempMailLolProvider {
+    pub(crate) const CAPABILITIES: Capabilities = Capabilities {
+        custom_name: true,
+        choose_domain: true,
+        custom_domains: true,
+        resumption: true,
+        ..Capabilities::NONE
+    };
+
+    /// The name is only a prefix, which the API extends with random characters, and
+    /// anything but letters and digits is dropped from it.
+    pub(crate) const NAME_RULES: NameRules = NameRules {
+        min_length: 1,
+        max_length: 12,
+        symbols: "",
+        uppercase: false,
+    };
+
     pub fn new() -> Self {
         Self {}
     }
+
+    /// Creates an inbox with `name` as prefix, on `domain` or on a domain chosen by the API.
+    async fn create_inbox(
+        &self,
+        name: &str,
+        domain: Option<&Domain>,
+    ) -> Result<Inbox, InboxCreationError> {
+        let mut body = json!({"prefix": name});
+        if let Some(domain) = domain {
+            body["domain"] = json!(domain.to_string());
+        }
+        let client = Client::builder().cookie_store(true).build()?;
+        let response = client
+            .post("https://api.tempmail.lol/v2/inbox/create")
+            .header("ACCEPT", "application/json")
+            .json(&body)
+            .send_step(ProviderType::TempMailLol, "create_inbox")
+            .await?;
+
+        if !response.status().is_success() {
+            return Err(
+                InboxCreationError::from_response(ProviderType::TempMailLol, response).await,
+            );
+        }
+
+        let inbox: InboxResponse = response.json().await?;
+        Ok(Inbox::new(
+            inbox.address.parse()?,
+            ProviderType::TempMailLol,
+            TempMailLolMessageFetcher {
+                client,
+                token: inbox.token,
+            },
+        )
+        .expiring_at(Some(
+            DateTime::<Utc>::from(SystemTime::now()) + INBOX_LIFETIME,
+        )))
+    }
 }
 
 #[derive(serde::Deserialize)]
 struct FetchResponse {
     emails: Vec<Email>,
+    #[serde(default)]
+    expired: bool,
 }
 
 #[derive(serde::Deserialize)]
-#[allow(dead_code)]
 struct Email {
     from: String,
     to: String,
@@ -38,10 +101,17 @@ struct Email {
 
 impl From<Email> for Message {
     fn from(email: Email) -> Self {
+        // The API does not return message ids, so they are derived from the message.
+        let hash = content_hash(&[&email.from, &email.subject, &email.body]);
         Self {
-            from: email.from,
+            id: format!("{}-{:016x}", email.date, hash),
+            from: Mailbox::from_header(&email.from),
+            to: Mailbox::list_from_header(&email.to),
+            cc: Vec::new(),
+            reply_to: Vec::new(),
             subject: email.subject,
             body: email.body,
+            date: DateTime::from_timestamp_millis(email.date as i64),
         }
     }
 }
@@ -58,76 +128,42 @@ impl Provider for TempMailLolProvider {
         &mut self,
         name: &str,
     ) -> Result<Inbox, InboxCreationError> {
-        let client = Client::builder().cookie_store(true).build()?;
-        let response = client
-            .post("https://api.tempmail.lol/v2/inbox/create")
-            .header("ACCEPT", "application/json")
-            .json(&json!({"prefix": name}))
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            return Err(InboxCreationError::CreationError(
-                response.text().await.unwrap_or_default(),
-            ));
-        }
-
-        let inbox: InboxResponse = response.json().await?;
-        Ok(Inbox {
-            message_fetcher: Arc::new(Mutex::new(TempMailLolMessageFetcher {
-                client,
-                token: inbox.token,
-            })),
-            email_address: inbox.address.parse()?,
-        })
+        self.create_inbox(name, None).await
     }
 
     async fn new_inbox(&mut self, name: &str, domain: Domain) -> Result<Inbox, InboxCreationError> {
-        let client = Client::builder().cookie_store(true).build()?;
-        let response = client
-            .post("https://api.tempmail.lol/v2/inbox/create")
-            .header("ACCEPT", "application/json")
-            .json(&json!({
-                "prefix": name,
-                "domain": domain.to_string(),
-            }))
-            .send()
-            .await?;
-
-        if !response.status().is_success() {
-            return Err(InboxCreationError::CreationError(
-                response.text().await.unwrap_or_default(),
-            ));
-        }
-
-        let inbox: InboxResponse = response.json().await?;
-        Ok(Inbox {
-            message_fetcher: Arc::new(Mutex::new(TempMailLolMessageFetcher {
-                client,
-                token: inbox.token,
-            })),
-            email_address: inbox.address.parse()?,
-        })
+        self.create_inbox(name, Some(&domain)).await
     }
 
-    fn get_domains(&self) -> Vec<Domain> {
-        vec![
-            Domain::TerribleCoffeeOrg,
-            Domain::UnderseaGolfCom,
-            Domain::JailBreakEverythingCom,
-            Domain::Awesome47,
-            Domain::ExpiredToasterOrg,
-            Domain::UndeadBankCom,
-        ]
-    }
-    fn get_random_domain(&self) -> Result<Domain, InboxCreationError> {
-        Err(InboxCreationError::DomainNotSupported)
+    async fn resume_inbox(
+        &mut self,
+        email_address: EmailAddress,
+        credentials: &str,
+    ) -> Result<Inbox, InboxCreationError> {
+        let client = Client::builder().cookie_store(true).build()?;
+        Ok(Inbox::new(
+            email_address,
+            ProviderType::TempMailLol,
+            TempMailLolMessageFetcher {
+                client,
+                token: credentials.to_string(),
+            },
+        ))
     }
     fn get_provider_type(&self) -> ProviderType {
         ProviderType::TempMailLol
     }
-    fn support_custom_domains(&self) -> bool {
-        true
+    async fn health_check(&self) -> HealthStatus {
+        // There is no status endpoint, so an inbox is requested without a token. The API
+        // answers this with a JSON error, which shows that it is up.
+        let request = Client::new()
+            .get("https://api.tempmail.lol/v2/inbox")
+            .header("ACCEPT", "application/json")
+            .query(&[("token", "")]);
+        probe(ProviderType::TempMailLol, request, |_, body| {
+            serde_json::from_str::<serde_json::Value>(body).is_ok()
+        })
+        .await
     }
 }
 
@@ -139,16 +175,45 @@ impl MessageFetcher for TempMailLolMessageFetcher {
             .get("https://api.tempmail.lol/v2/inbox")
             .header("ACCEPT", "application/json")
             .query(&[("token", &self.token)])
-            .send()
+            .send_step(ProviderType::TempMailLol, "fetch_messages")
             .await?;
 
         if !response.status().is_success() {
-            return Err(MessageFetcherError::InvalidResponseStatus(
-                response.status(),
-            ));
+            return Err(
+                MessageFetcherError::from_response(ProviderType::TempMailLol, response).await,
+            );
         }
 
         let response: FetchResponse = response.json().await?;
+        if response.expired {
+            return Err(MessageFetcherError::InboxExpired);
+        }
         Ok(response.emails.into_iter().map(Into::into).collect())
     }
+
+    fn get_credentials(&self) -> String {
+        self.token.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_message_id_is_stable() {
+        let email = || Email {
+            from: "sender@example.com".to_string(),
+            to: "test@underseagolf.com".to_string(),
+            subject: "Your code".to_string(),
+            body: "123456".to_string(),
+            date: 1_700_000_000_000,
+        };
+        assert_eq!("1700000000000-48948a454834b89c", Message::from(email()).id);
+        let other = Email {
+            body: "654321".to_string(),
+            ..email()
+        };
+        assert_ne!(Message::from(email()).id, Message::from(other).id);
+    }
 }
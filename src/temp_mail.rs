@@ -1,4 +1,27 @@
-use crate::{domain::Domain, error::InboxCreationError, provider::ProviderType, Inbox};
+use std::fmt::{self, Debug, Formatter};
+use std::sync::{Arc, Mutex, PoisonError};
+
+use rand::rngs::StdRng;
+use rand::seq::IndexedRandom;
+use rand::{RngCore, SeedableRng};
+
+use crate::{
+    capabilities::Capability,
+    domain::Domain,
+    error::InboxCreationError,
+    metrics,
+    name::{NameGenerator, RandomName},
+    provider::{Provider, ProviderOptions, ProviderType},
+    trace::{span, Instrument},
+    Inbox, InboxSession,
+};
+
+/// The provider, domain and name chosen for a new inbox.
+struct InboxPlan {
+    provider: Box<dyn Provider>,
+    domain: Option<Domain>,
+    name: String,
+}
 
 /// A builder for creating temporary email inboxes.
 ///
@@ -22,11 +45,29 @@ use crate::{domain::Domain, error::InboxCreationError, provider::ProviderType, I
 ///         .unwrap();
 /// }
 /// ```
-#[derive(Debug, Clone, Default)]
+#[derive(Clone, Default)]
 pub struct TempMail {
     provider_type: Option<ProviderType>,
     domain: Option<Domain>,
     name: Option<String>,
+    name_generator: Option<Arc<dyn NameGenerator>>,
+    required: Vec<Capability>,
+    rng: Option<Arc<Mutex<dyn RngCore + Send>>>,
+    options: ProviderOptions,
+}
+
+impl Debug for TempMail {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        f.debug_struct("TempMail")
+            .field("provider_type", &self.provider_type)
+            .field("domain", &self.domain)
+            .field("name", &self.name)
+            .field("name_generator", &self.name_generator.is_some())
+            .field("required", &self.required)
+            .field("rng", &self.rng.is_some())
+            .field("options", &self.options)
+            .finish()
+    }
 }
 
 impl TempMail {
@@ -66,44 +107,324 @@ impl TempMail {
         self
     }
 
+    /// Sets the generator for the name, if no name is set with [`TempMail::name`].
+    ///
+    /// The generated name is adjusted to the naming rules of the chosen provider, see
+    /// [`ProviderType::name_rules`]. If not set, 8 random lowercase letters and digits are used.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use ephemeral_email::name::RealisticName;
+    /// use ephemeral_email::TempMail;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let inbox = TempMail::new()
+    ///         .name_generator(RealisticName::new().digits(2))
+    ///         .create_inbox()
+    ///         .await
+    ///         .unwrap();
+    /// }
+    /// ```
+    pub fn name_generator(mut self, name_generator: impl NameGenerator + 'static) -> Self {
+        self.name_generator = Some(Arc::new(name_generator));
+        self
+    }
+
+    /// Requires the provider to support `capability`. Can be called multiple times.
+    ///
+    /// If no provider type is set, only providers supporting all required capabilities are
+    /// chosen. If a provider type is set that lacks one of them, [`TempMail::create_inbox`]
+    /// fails without sending any requests.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use ephemeral_email::{Capability, TempMail};
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let inbox = TempMail::new()
+    ///         .require(Capability::Deletion)
+    ///         .create_inbox()
+    ///         .await
+    ///         .unwrap();
+    ///     inbox.delete().await.unwrap();
+    /// }
+    /// ```
+    pub fn require(mut self, capability: Capability) -> Self {
+        if !self.required.contains(&capability) {
+            self.required.push(capability);
+        }
+        self
+    }
+
+    /// Sets how many message details the inbox fetches at the same time.
+    ///
+    /// Some providers need a separate request for every message, which are sent concurrently up to
+    /// this limit. The messages are always returned in the order of the provider. Defaults to 4.
+    pub fn fetch_concurrency(mut self, fetch_concurrency: usize) -> Self {
+        self.options.fetch_concurrency = fetch_concurrency.max(1);
+        self
+    }
+
+    /// Points a Mail.tm-compatible provider at the API at `url`, like a self-hosted instance
+    /// for testing.
+    ///
+    /// Only providers supporting [`Capability::CustomApi`] are chosen, and Mail.tm if no provider
+    /// type is set. New messages are polled, unless a Mercure hub is set with
+    /// [`TempMail::mercure_url`]. The URL is stored in the [`crate::InboxSession`], so the inbox
+    /// can be resumed.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use ephemeral_email::TempMail;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let inbox = TempMail::new()
+    ///         .api_url("http://localhost:8080")
+    ///         .create_inbox()
+    ///         .await
+    ///         .unwrap();
+    /// }
+    /// ```
+    pub fn api_url(mut self, url: impl Into<String>) -> Self {
+        self.options.api_url = Some(url.into());
+        self.provider_type.get_or_insert(ProviderType::MailTm);
+        self.require(Capability::CustomApi)
+    }
+
+    /// Sets the Mercure hub notifying about new messages on the API set with [`TempMail::api_url`].
+    pub fn mercure_url(mut self, url: impl Into<String>) -> Self {
+        self.options.mercure_url = Some(url.into());
+        self
+    }
+
+    /// Seeds the random number generator used to choose the provider, domain and name.
+    ///
+    /// With the same seed and settings, the same provider, domain and name are chosen every
+    /// time, which makes failing runs reproducible. Clones of the builder share the generator,
+    /// so inboxes created from clones continue the same sequence.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use ephemeral_email::TempMail;
+    ///
+    /// #[tokio::main]
+    /// async fn main() {
+    ///     let seed = std::env::var("SEED").map_or(42, |seed| seed.parse().unwrap());
+    ///     let inbox = TempMail::new().seed(seed).create_inbox().await.unwrap();
+    /// }
+    /// ```
+    pub fn seed(self, seed: u64) -> Self {
+        self.rng(StdRng::seed_from_u64(seed))
+    }
+
+    /// Sets the random number generator used to choose the provider, domain and name.
+    ///
+    /// Defaults to the thread-local generator of the `rand` crate.
+    pub fn rng(mut self, rng: impl RngCore + Send + 'static) -> Self {
+        self.rng = Some(Arc::new(Mutex::new(rng)));
+        self
+    }
+
     /// Creates a new inbox with the specified attributes.
     /// If no attributes are set, random values will be chosen.
     /// If a domain and provider is specified, they must be compatible.
     /// # Returns
     /// A new inbox, or an [`InboxCreationError`] error if the inbox could not be created.
     pub async fn create_inbox(self) -> Result<Inbox, InboxCreationError> {
+        let InboxPlan {
+            mut provider,
+            domain,
+            name,
+        } = match &self.rng {
+            Some(rng) => self.plan(&mut *rng.lock().unwrap_or_else(PoisonError::into_inner)),
+            None => self.plan(&mut rand::rng()),
+        }?;
+
+        let provider_type = provider.get_provider_type();
+        metrics::record_creation_attempt(provider_type);
+        let span = span!(
+            "create_inbox",
+            provider = %provider_type,
+            domain = ?domain,
+            name = %name,
+        );
+        async {
+            let result = match domain {
+                Some(domain) => provider.new_inbox(&name, domain).await,
+                None => provider.new_random_inbox_from_name(&name).await,
+            };
+            #[cfg(feature = "tracing")]
+            match &result {
+                Ok(inbox) => {
+                    tracing::info!(address = %inbox.get_email_address(), "inbox created")
+                }
+                Err(error) => tracing::warn!(error = %error, "inbox creation failed"),
+            }
+            metrics::record_creation(provider_type, &result);
+            result
+        }
+        .instrument(span)
+        .await
+    }
+
+    /// Resumes an inbox from a session previously returned by [`Inbox::get_session`].
+    ///
+    /// Unlike [`Inbox::resume`], the inbox uses the settings of this builder that apply to existing
+    /// inboxes, like [`TempMail::fetch_concurrency`]. The settings choosing the provider, domain
+    /// and name are ignored.
+    ///
+    /// # Example
+    /// ```no_run
+    /// use ephemeral_email::{InboxSession, TempMail};
+    ///
+    /// async fn example(session: InboxSession) {
+    ///     let inbox = TempMail::new()
+    ///         .fetch_concurrency(8)
+    ///         .resume_inbox(session)
+    ///         .await
+    ///         .unwrap();
+    /// }
+    /// ```
+    pub async fn resume_inbox(self, session: InboxSession) -> Result<Inbox, InboxCreationError> {
+        Inbox::resume_with_options(session, self.options).await
+    }
+
+    /// Chooses the provider, domain and name before any request is sent, so the choices only
+    /// depend on `rng`.
+    fn plan(&self, rng: &mut dyn RngCore) -> Result<InboxPlan, InboxCreationError> {
+        let candidates: Vec<ProviderType> = ProviderType::get_all_providers()
+            .into_iter()
+            .filter(|provider_type| {
+                provider_type
+                    .capabilities()
+                    .first_missing(&self.required)
+                    .is_none()
+            })
+            .collect();
         let provider_type = match (self.provider_type, &self.domain) {
-            (Some(provider_type), None) => provider_type,
-            (Some(provider_type), Some(_)) => provider_type,
-            (None, None) => rand::random(),
-            (None, Some(domain)) => {
-                let provider_types = ProviderType::get_all_providers();
-                *provider_types
-                    .iter()
-                    .find(|provider_type| {
-                        provider_type.get_provider().get_domains().contains(domain)
-                    })
-                    .ok_or(InboxCreationError::NoProviderForDomain(domain.to_string()))?
+            (Some(provider_type), _) => {
+                if let Some(capability) = provider_type.capabilities().first_missing(&self.required)
+                {
+                    return Err(InboxCreationError::CapabilityNotSupported(
+                        provider_type,
+                        capability,
+                    ));
+                }
+                provider_type
             }
+            (None, None) => *candidates
+                .choose(rng)
+                .ok_or(InboxCreationError::NoProviderWithCapabilities)?,
+            (None, Some(domain)) => domain
+                .providers()
+                .into_iter()
+                .find(|provider_type| candidates.contains(provider_type))
+                .ok_or(InboxCreationError::NoProviderForDomain(domain.to_string()))?,
         };
-        let mut provider = provider_type.get_provider();
-
-        if let Some(ref domain) = self.domain {
-            if !(provider.get_domains().contains(domain)
-                || provider.support_custom_domains() && matches!(domain, Domain::Custom(_)))
-            {
-                return Err(InboxCreationError::InvalidDomainForProvider(
-                    domain.to_string(),
-                    provider.get_provider_type(),
-                ));
+        let provider = provider_type.get_provider_with_options(self.options.clone());
+
+        let domain = match &self.domain {
+            Some(domain) => {
+                if !(provider.get_domains().contains(domain)
+                    || provider.capabilities().custom_domains
+                        && matches!(domain, Domain::Custom(_)))
+                {
+                    return Err(InboxCreationError::InvalidDomainForProvider(
+                        domain.to_string(),
+                        provider.get_provider_type(),
+                    ));
+                }
+                Some(domain.clone())
             }
-        }
+            None => provider.get_random_domain(rng).ok(),
+        };
+
+        let name = match (&self.name, &self.name_generator) {
+            (Some(name), _) => name.clone(),
+            (None, Some(name_generator)) => provider
+                .name_rules()
+                .apply(&name_generator.generate(rng), rng),
+            (None, None) => provider
+                .name_rules()
+                .apply(&RandomName::default().generate(rng), rng),
+        };
+
+        Ok(InboxPlan {
+            provider,
+            domain,
+            name,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::DomainCatalog;
+
+    fn plan(temp_mail: &TempMail, seed: u64) -> (ProviderType, Option<Domain>, String) {
+        let plan = temp_mail.plan(&mut StdRng::seed_from_u64(seed)).unwrap();
+        (plan.provider.get_provider_type(), plan.domain, plan.name)
+    }
 
-        match (self.name, self.domain) {
-            (Some(name), Some(domain)) => provider.new_inbox(&name, domain).await,
-            (Some(name), None) => provider.new_random_inbox_from_name(&name).await,
-            (None, Some(domain)) => provider.new_random_inbox_from_domain(domain).await,
-            (None, None) => provider.new_random_inbox().await,
+    #[test]
+    fn test_plan_is_reproducible() {
+        let temp_mail = TempMail::new();
+        for seed in 0..10 {
+            assert_eq!(plan(&temp_mail, seed), plan(&temp_mail, seed));
         }
     }
+
+    #[test]
+    fn test_generated_name_follows_provider_rules() {
+        let temp_mail =
+            TempMail::new().name_generator(|_: &mut dyn RngCore| "Jane.Doe-Smith_1990".to_string());
+        let name = |provider_type| plan(&temp_mail.clone().provider_type(provider_type), 0).2;
+        assert_eq!("janedoesmith", name(ProviderType::TempMailLol));
+        assert_eq!("jane.doesmith_1990", name(ProviderType::FakeMailNet));
+        assert_eq!("jane.doe-smith_1990", name(ProviderType::MailTm));
+    }
+
+    #[test]
+    fn test_domain_is_chosen_by_capabilities() {
+        let domain = |provider_type| plan(&TempMail::new().provider_type(provider_type), 0).1;
+        let domain_of_tempmail_lol = domain(ProviderType::TempMailLol).unwrap();
+        assert!(DomainCatalog::bundled()
+            .domains(ProviderType::TempMailLol)
+            .contains(&domain_of_tempmail_lol));
+        assert_eq!(None, domain(ProviderType::MailTm));
+    }
+
+    #[test]
+    fn test_custom_api() {
+        let temp_mail = TempMail::new().api_url("http://localhost:8080");
+        assert_eq!(ProviderType::MailTm, plan(&temp_mail, 0).0);
+
+        let temp_mail = TempMail::new()
+            .provider_type(ProviderType::TempMailLol)
+            .api_url("http://localhost:8080");
+        assert!(matches!(
+            temp_mail.plan(&mut StdRng::seed_from_u64(0)),
+            Err(InboxCreationError::CapabilityNotSupported(
+                ProviderType::TempMailLol,
+                Capability::CustomApi
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_shared_rng_continues_sequence() {
+        let temp_mail = TempMail::new().seed(7);
+        let rng = temp_mail.rng.clone().unwrap();
+        let first = temp_mail.plan(&mut *rng.lock().unwrap()).unwrap().name;
+        let second = temp_mail
+            .clone()
+            .plan(&mut *rng.lock().unwrap())
+            .unwrap()
+            .name;
+        assert_ne!(first, second);
+    }
 }
@@ -0,0 +1,125 @@
+//! Instrumentation with the `tracing` crate, which compiles to nothing without the `tracing`
+//! feature.
+//!
+//! Tokens, cookies and other credentials are never recorded. Query parameters of request URLs
+//! are redacted, as some providers pass tokens in them.
+
+#[cfg(feature = "tracing")]
+use std::time::Instant;
+
+#[cfg(feature = "tracing")]
+pub(crate) use tracing::{Instrument, Span};
+
+use crate::client::{Error, RequestBuilder, Response, Url};
+use crate::provider::ProviderType;
+
+/// Emits a `tracing` event, like `tracing::debug!(...)` for `event!(debug, ...)`.
+macro_rules! event {
+    ($level:ident, $($arg:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        tracing::$level!($($arg)+);
+    }};
+}
+
+/// Creates an info level span, like `tracing::info_span!(...)`.
+macro_rules! span {
+    ($($arg:tt)+) => {{
+        #[cfg(feature = "tracing")]
+        let span = tracing::info_span!($($arg)+);
+        #[cfg(not(feature = "tracing"))]
+        let span = $crate::trace::Span;
+        span
+    }};
+}
+
+pub(crate) use {event, span};
+
+/// Stands in for `tracing::Span` without the `tracing` feature.
+#[cfg(not(feature = "tracing"))]
+pub(crate) struct Span;
+
+/// Stands in for `tracing::Instrument` without the `tracing` feature.
+#[cfg(not(feature = "tracing"))]
+pub(crate) trait Instrument: Sized {
+    fn instrument(self, _span: Span) -> Self {
+        self
+    }
+}
+
+#[cfg(not(feature = "tracing"))]
+impl<T> Instrument for T {}
+
+/// Sends requests as a named step of a provider flow.
+pub(crate) trait SendStep {
+    /// Sends the request, recording the provider, step, status code and latency.
+    async fn send_step(
+        self,
+        provider_type: ProviderType,
+        step: &'static str,
+    ) -> Result<Response, Error>;
+}
+
+impl SendStep for RequestBuilder {
+    #[cfg_attr(not(feature = "tracing"), allow(unused_variables))]
+    async fn send_step(
+        self,
+        provider_type: ProviderType,
+        step: &'static str,
+    ) -> Result<Response, Error> {
+        #[cfg(feature = "tracing")]
+        let start = Instant::now();
+        let result = self.send().await;
+        #[cfg(feature = "tracing")]
+        {
+            let latency_ms = start.elapsed().as_millis() as u64;
+            match &result {
+                Ok(response) => tracing::debug!(
+                    provider = %provider_type,
+                    step,
+                    url = %redact_url(response.url()),
+                    status = response.status().as_u16(),
+                    latency_ms,
+                    "request completed"
+                ),
+                Err(error) => tracing::warn!(
+                    provider = %provider_type,
+                    step,
+                    timeout = error.is_timeout(),
+                    connect = error.is_connect(),
+                    latency_ms,
+                    "request failed"
+                ),
+            }
+        }
+        result
+    }
+}
+
+/// Returns `url` with the values of all query parameters replaced.
+pub(crate) fn redact_url(url: &Url) -> String {
+    let mut url = url.clone();
+    let keys: Vec<String> = url.query_pairs().map(|(key, _)| key.into_owned()).collect();
+    if !keys.is_empty() {
+        url.query_pairs_mut()
+            .clear()
+            .extend_pairs(keys.iter().map(|key| (key, "redacted")));
+    }
+    url.to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_url() {
+        let url =
+            Url::parse("https://api.tempmail.lol/v2/inbox?token=secret&csrf_token=abc").unwrap();
+        assert_eq!(
+            redact_url(&url),
+            "https://api.tempmail.lol/v2/inbox?token=redacted&csrf_token=redacted"
+        );
+        let url = Url::parse("https://api.mail.tm/messages").unwrap();
+        assert_eq!(redact_url(&url), "https://api.mail.tm/messages");
+    }
+}